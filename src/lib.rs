@@ -0,0 +1,1083 @@
+// fatcat - Hunt down the fat files hogging your disk space
+// Copyright (C) 2024  rxxuzi
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+//! Scanning core of fatcat.
+//!
+//! ```no_run
+//! let opts = fatcat::ScanOptions {
+//!     roots: vec!["/home".into()],
+//!     min_size: 500 * 1024 * 1024,
+//!     ..Default::default()
+//! };
+//! for file in fatcat::scan(&opts).files {
+//!     println!("{}  {}", fatcat::format_size(file.size), file.path.display());
+//! }
+//! ```
+
+use globset::GlobSet;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::{Match, WalkBuilder};
+use jwalk::{Parallelism, WalkDirGeneric};
+use regex::Regex;
+use serde::{Serialize, Serializer};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::{self, FileType};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// A matched file.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileInfo {
+    #[serde(serialize_with = "serialize_path")]
+    pub path: PathBuf,
+    pub size: u64,
+    #[serde(serialize_with = "serialize_time")]
+    pub modified: Option<SystemTime>,
+    /// Set with [`ScanOptions::read_owner`] on Unix.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<Owner>,
+    /// Device and inode of a file with several hard links, see [`inode_key`].
+    #[serde(skip)]
+    pub inode: Option<(u64, u64)>,
+}
+
+/// Numeric owner and group of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Owner {
+    pub uid: u32,
+    pub gid: u32,
+}
+
+fn serialize_path<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&path.to_string_lossy())
+}
+
+fn serialize_time<S: Serializer>(
+    time: &Option<SystemTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let secs = time
+        .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs());
+    secs.serialize(serializer)
+}
+
+/// Unit convention for [`format_size_with`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    /// Powers of 1024, labelled `KB`, `MB`, `GB`, `TB`.
+    #[default]
+    Binary,
+    /// Powers of 1000 as used by disk vendors, labelled `kB`, `MB`, `GB`, `TB`.
+    Si,
+}
+
+/// Formats a byte count with binary units, e.g. `1.50 GB`.
+pub fn format_size(bytes: u64) -> String {
+    format_size_with(bytes, Units::Binary)
+}
+
+/// Formats a byte count with the given unit convention.
+pub fn format_size_with(bytes: u64, units: Units) -> String {
+    let (base, labels) = match units {
+        Units::Binary => (1024u64, ["KB", "MB", "GB", "TB"]),
+        Units::Si => (1000u64, ["kB", "MB", "GB", "TB"]),
+    };
+
+    let mut unit = 1u64;
+    let mut label = None;
+    for name in labels {
+        if bytes < unit * base {
+            break;
+        }
+        unit *= base;
+        label = Some(name);
+    }
+    match label {
+        Some(label) => format!("{:.2} {}", bytes as f64 / unit as f64, label),
+        None => format!("{} B", bytes),
+    }
+}
+
+/// What to scan and which files to keep.
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    pub roots: Vec<PathBuf>,
+    pub min_size: u64,
+    pub max_size: Option<u64>,
+    /// Also match zero-byte files, which are skipped otherwise.
+    pub include_zero: bool,
+    /// Lowercase extensions without the leading dot.
+    pub extensions: Option<Vec<String>>,
+    /// Lowercase extensions to skip, even if listed in `extensions`.
+    pub excluded_extensions: Vec<String>,
+    /// Only files whose name matches, or whose full path matches when
+    /// `match_path` is set.
+    pub name_pattern: Option<Regex>,
+    pub match_path: bool,
+    /// Only files whose full path matches one of these globs.
+    pub include_globs: Option<GlobSet>,
+    /// Files whose full path matches one of these globs are skipped, even if
+    /// they also match `include_globs`.
+    pub exclude_globs: Option<GlobSet>,
+    /// Directory names that are pruned from the walk.
+    pub excludes: Vec<String>,
+    /// Files of ignore patterns applied below every root, as if each were a
+    /// [`IGNORE_FILE_NAME`] file in the root itself.
+    pub ignore_files: Vec<PathBuf>,
+    pub older_than: Option<Duration>,
+    pub newer_than: Option<Duration>,
+    /// Only files last accessed at least this long ago.
+    pub accessed_before: Option<Duration>,
+    /// Honor `.gitignore`, `.ignore` and global git excludes.
+    pub gitignore: bool,
+    /// Skip dotfiles, plus files with the hidden attribute on Windows.
+    pub skip_hidden_files: bool,
+    /// Skip dot-directories and everything below them, plus directories
+    /// with the hidden attribute on Windows.
+    pub skip_hidden_dirs: bool,
+    /// Measure allocated disk space (`st_blocks * 512`, like `du`) instead of
+    /// the apparent file length. Only supported on Unix.
+    pub disk_usage: bool,
+    /// Do not descend into directories on other filesystems, like `du -x`.
+    /// Only supported on Unix.
+    pub one_file_system: bool,
+    /// Count the size of a hard-linked file only once. Every path is still
+    /// listed. Only supported on Unix.
+    pub count_links_once: bool,
+    /// Traverse symlinked directories and count symlinked files. Every file
+    /// is still reported once, however many links lead to it.
+    pub follow_links: bool,
+    /// Only files owned by this user id. Only supported on Unix.
+    pub owner_uid: Option<u32>,
+    /// Record the owner of every matched file in [`FileInfo::owner`]. Only
+    /// supported on Unix.
+    pub read_owner: bool,
+    /// Descend at most this many levels; 1 means only the root's children.
+    pub max_depth: Option<usize>,
+    /// Depth limits for single roots, keyed by the root as it appears in
+    /// `roots`. They replace `max_depth` for that root.
+    pub root_depths: HashMap<PathBuf, usize>,
+    /// Only match files at least this many levels below the root, counted
+    /// like `max_depth`.
+    pub min_depth: Option<usize>,
+    pub threads: Option<usize>,
+    /// Fill [`ScanResult::dir_sizes`] with the total size of every directory.
+    pub aggregate_dirs: bool,
+    /// Fill [`ScanResult::empty_dirs`] with every directory that has no
+    /// entries at all.
+    pub find_empty_dirs: bool,
+    /// Keep only the `limit` largest files instead of every match.
+    pub limit: Option<usize>,
+    /// Counters updated while the scan runs, for progress display.
+    pub progress: Option<Arc<Progress>>,
+    /// Ends the scan early once set, e.g. from a Ctrl-C handler. The files
+    /// found so far are still returned, see [`ScanResult::interrupted`].
+    pub stop: Option<Arc<AtomicBool>>,
+    /// Called with every matched file as soon as it is found, before any
+    /// sorting or limit. A file reached again through overlapping roots is
+    /// passed only once.
+    pub on_match: Option<OnMatch>,
+    /// Add up the time spent reading metadata in
+    /// [`ScanResult::metadata_time`].
+    pub profile: bool,
+}
+
+/// Callback for [`ScanOptions::on_match`].
+#[derive(Clone)]
+pub struct OnMatch(pub Arc<dyn Fn(&FileInfo) + Send + Sync>);
+
+impl fmt::Debug for OnMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OnMatch(..)")
+    }
+}
+
+/// Live scan counters, see [`ScanOptions::progress`].
+#[derive(Debug, Default)]
+pub struct Progress {
+    /// Files visited so far.
+    pub files: AtomicU64,
+    /// Combined size of the files visited so far.
+    pub bytes: AtomicU64,
+    /// The directory most recently entered by the walk.
+    pub dir: Mutex<PathBuf>,
+}
+
+impl ScanOptions {
+    /// How many levels to descend below `root`, if limited.
+    fn max_depth_for(&self, root: &Path) -> Option<usize> {
+        self.root_depths.get(root).copied().or(self.max_depth)
+    }
+
+    /// Whether [`ScanOptions::stop`] has been set.
+    fn stopped(&self) -> bool {
+        self.stop
+            .as_ref()
+            .is_some_and(|stop| stop.load(Ordering::Relaxed))
+    }
+
+    pub fn size_in_range(&self, size: u64) -> bool {
+        (size > 0 || self.include_zero)
+            && size >= self.min_size
+            && self.max_size.is_none_or(|max| size <= max)
+    }
+
+    pub fn filters_by_age(&self) -> bool {
+        self.older_than.is_some() || self.newer_than.is_some()
+    }
+
+    fn globs_allow(&self, path: &Path) -> bool {
+        if self
+            .exclude_globs
+            .as_ref()
+            .is_some_and(|g| g.is_match(path))
+        {
+            return false;
+        }
+        self.include_globs.as_ref().is_none_or(|g| g.is_match(path))
+    }
+
+    fn age_in_range(&self, now: SystemTime, modified: SystemTime) -> bool {
+        let age = now.duration_since(modified).unwrap_or(Duration::ZERO);
+        self.older_than.is_none_or(|min| age >= min) && self.newer_than.is_none_or(|max| age <= max)
+    }
+}
+
+/// Outcome of [`scan`]. `files` is sorted by size, largest first.
+#[derive(Debug, Default)]
+pub struct ScanResult {
+    pub files: Vec<FileInfo>,
+    pub files_scanned: u64,
+    pub dirs_scanned: u64,
+    /// Combined size of every file whose metadata was read, matched or not.
+    /// Files rejected by the extension, name or glob filters are not read.
+    pub bytes_scanned: u64,
+    /// Number of matched files, which may exceed `files.len()` when a limit is set.
+    pub matched: u64,
+    /// Combined size of every matched file, including those beyond the limit.
+    pub matched_bytes: u64,
+    /// Matched files dropped because an overlapping root already listed them.
+    pub overlapping: u64,
+    /// Files skipped by the age filter because their mtime could not be read.
+    pub mtime_skipped: u64,
+    /// Files skipped by the access time filter because their atime could not
+    /// be read.
+    pub atime_skipped: u64,
+    /// Files whose atime was checked, and how many of those had an atime
+    /// equal to their mtime, see [`ScanResult::atime_unreliable`].
+    pub atime_checked: u64,
+    pub atime_matches_mtime: u64,
+    /// Matched files that are further hard links to an already counted inode,
+    /// with [`ScanOptions::count_links_once`].
+    pub links_collapsed: u64,
+    /// Combined size of those files, to subtract from totals.
+    pub linked_bytes: u64,
+    /// Entries that could not be read, e.g. because of missing permissions.
+    pub errors: u64,
+    /// The first [`MAX_ERROR_SAMPLES`] of those errors.
+    pub error_samples: Vec<ScanError>,
+    pub dir_sizes: HashMap<PathBuf, u64>,
+    /// Sorted by path.
+    pub empty_dirs: Vec<PathBuf>,
+    /// Time spent reading file metadata, with [`ScanOptions::profile`].
+    pub metadata_time: Duration,
+    /// Whether [`ScanOptions::stop`] ended the scan before every root was
+    /// walked, so the results are partial.
+    pub interrupted: bool,
+}
+
+/// How many unreadable entries [`ScanResult::error_samples`] keeps.
+pub const MAX_ERROR_SAMPLES: usize = 5;
+
+/// An entry that could not be read during the walk.
+#[derive(Debug, Clone)]
+pub struct ScanError {
+    pub path: PathBuf,
+    /// Short description such as `permission denied` or `symlink loop`.
+    pub reason: String,
+}
+
+impl ScanError {
+    fn io(path: PathBuf, err: &io::Error) -> Self {
+        ScanError {
+            path,
+            reason: err.kind().to_string(),
+        }
+    }
+
+    fn symlink_loop(path: PathBuf) -> Self {
+        ScanError {
+            path,
+            reason: "symlink loop".to_string(),
+        }
+    }
+}
+
+impl ScanResult {
+    /// Whether nearly every atime equals its mtime, which suggests a
+    /// `noatime` mount where access times are never updated.
+    pub fn atime_unreliable(&self) -> bool {
+        self.atime_checked >= 10 && self.atime_matches_mtime * 10 >= self.atime_checked * 9
+    }
+
+    fn record_error(&mut self, error: ScanError) {
+        self.errors += 1;
+        if self.error_samples.len() < MAX_ERROR_SAMPLES {
+            self.error_samples.push(error);
+        }
+    }
+}
+
+fn same_second(a: Option<SystemTime>, b: Option<SystemTime>) -> bool {
+    let secs = |t: Option<SystemTime>| {
+        t.and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+    };
+    secs(a).is_some() && secs(a) == secs(b)
+}
+
+fn has_extension(path: &Path, extensions: &[String]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| extensions.iter().any(|e| e.eq_ignore_ascii_case(ext)))
+        .unwrap_or(false)
+}
+
+fn matches_pattern(path: &Path, pattern: &Regex, full_path: bool) -> bool {
+    if full_path {
+        return pattern.is_match(&path.to_string_lossy());
+    }
+    path.file_name()
+        .is_some_and(|name| pattern.is_match(&name.to_string_lossy()))
+}
+
+struct WalkEntry {
+    path: PathBuf,
+    file_type: FileType,
+}
+
+/// Name of the per-directory files of gitignore-style patterns that every
+/// scan honors.
+pub const IGNORE_FILE_NAME: &str = ".fatcatignore";
+
+/// Ignore matchers in effect for a directory, outermost first.
+type IgnoreStack = Vec<Arc<Gitignore>>;
+
+/// Reads the files of ignore patterns in `paths` with patterns relative to
+/// `root`. Unreadable files and invalid patterns are skipped.
+fn build_ignore(root: &Path, paths: &[PathBuf]) -> Option<Arc<Gitignore>> {
+    let mut builder = GitignoreBuilder::new(root);
+    for path in paths {
+        // Partial errors still leave the valid patterns in place.
+        let _ = builder.add(path);
+    }
+    builder
+        .build()
+        .ok()
+        .filter(|ignore| !ignore.is_empty())
+        .map(Arc::new)
+}
+
+/// Whether the innermost matcher with an opinion on `path` ignores it.
+fn is_ignored(ignores: &[Arc<Gitignore>], path: &Path, is_dir: bool) -> bool {
+    for ignore in ignores.iter().rev() {
+        match ignore.matched(path, is_dir) {
+            Match::Ignore(_) => return true,
+            Match::Whitelist(_) => return false,
+            Match::None => {}
+        }
+    }
+    false
+}
+
+fn is_excluded(name: &OsStr, excludes: &[String]) -> bool {
+    name.to_str()
+        .is_some_and(|name| excludes.iter().any(|e| e == name))
+}
+
+fn jwalk_error(fallback: &Path, err: jwalk::Error) -> ScanError {
+    let path = err.path().unwrap_or(fallback).to_path_buf();
+    match err.io_error() {
+        Some(io_err) => ScanError::io(path, io_err),
+        None if err.loop_ancestor().is_some() => ScanError::symlink_loop(path),
+        None => ScanError {
+            path,
+            reason: err.to_string(),
+        },
+    }
+}
+
+fn ignore_error(root: &Path, err: ignore::Error) -> ScanError {
+    let mut inner = &err;
+    let path = loop {
+        match inner {
+            ignore::Error::WithPath { path, .. } => break path.clone(),
+            ignore::Error::Loop { child, .. } => return ScanError::symlink_loop(child.clone()),
+            ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+                inner = err
+            }
+            _ => break root.to_path_buf(),
+        }
+    };
+    match err.io_error() {
+        Some(io_err) => ScanError::io(path, io_err),
+        None => ScanError {
+            path,
+            reason: err.to_string(),
+        },
+    }
+}
+
+#[cfg(unix)]
+fn device_id(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path).ok().map(|m| m.dev())
+}
+
+#[cfg(not(unix))]
+fn device_id(_: &Path) -> Option<u64> {
+    None
+}
+
+fn is_hidden_name(name: &OsStr) -> bool {
+    name.as_encoded_bytes().first() == Some(&b'.')
+}
+
+/// Windows hides entries with an attribute rather than a leading dot.
+#[cfg(windows)]
+fn has_hidden_attribute(entry: &JwalkEntry) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    entry
+        .metadata()
+        .is_ok_and(|m| m.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+}
+
+#[cfg(not(windows))]
+fn has_hidden_attribute(_: &JwalkEntry) -> bool {
+    false
+}
+
+/// jwalk state: the ignore matchers of a directory and, per entry, whether
+/// the entry is a symlink back to one of its own ancestors.
+type JwalkEntry = jwalk::DirEntry<(IgnoreStack, bool)>;
+
+/// Stops jwalk from descending into symlinked directories that point back
+/// to an ancestor. jwalk's own check only compares the raw link target, so
+/// relative links like `sub/loop -> ..` would otherwise recurse until the OS
+/// gives up.
+fn mark_link_loops(dir: &Path, children: &mut [jwalk::Result<JwalkEntry>]) {
+    let mut dir_canonical: Option<PathBuf> = None;
+    for child in children.iter_mut().flatten() {
+        if !(child.file_type().is_dir() && child.path_is_symlink()) {
+            continue;
+        }
+        let dir_canonical = dir_canonical
+            .get_or_insert_with(|| fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf()));
+        if fs::canonicalize(child.path()).is_ok_and(|target| dir_canonical.starts_with(target)) {
+            child.read_children_path = None;
+            child.client_state = true;
+        }
+    }
+}
+
+fn walk_jwalk(
+    root: &Path,
+    opts: &ScanOptions,
+) -> impl Iterator<Item = Result<WalkEntry, ScanError>> {
+    let root_ignores: IgnoreStack = build_ignore(root, &opts.ignore_files).into_iter().collect();
+    // Hidden entries are filtered below, where files and directories can be
+    // told apart.
+    let mut walker = WalkDirGeneric::<(IgnoreStack, bool)>::new(root)
+        .skip_hidden(false)
+        .follow_links(opts.follow_links)
+        .root_read_dir_state(root_ignores);
+    if let Some(depth) = opts.max_depth_for(root) {
+        walker = walker.max_depth(depth);
+    }
+    if let Some(threads) = opts.threads {
+        walker = walker.parallelism(match threads {
+            1 => Parallelism::Serial,
+            n => Parallelism::RayonNewPool(n),
+        });
+    }
+    let root_device = if opts.one_file_system {
+        device_id(root)
+    } else {
+        None
+    };
+    // Always installed, as any directory may hold an ignore file.
+    let excludes = opts.excludes.clone();
+    let follow_links = opts.follow_links;
+    let (skip_hidden_files, skip_hidden_dirs) = (opts.skip_hidden_files, opts.skip_hidden_dirs);
+    walker = walker.process_read_dir(move |depth, dir, ignores, children| {
+        let has_ignore_file = children.iter().any(|child| {
+            child.as_ref().is_ok_and(|entry| {
+                entry.file_name == IGNORE_FILE_NAME && entry.file_type().is_file()
+            })
+        });
+        if has_ignore_file {
+            // Cloned into the state of every subdirectory from here on.
+            ignores.extend(build_ignore(dir, &[dir.join(IGNORE_FILE_NAME)]));
+        }
+        children.retain(|child| match child {
+            Ok(entry) => {
+                let is_dir = entry.file_type().is_dir();
+                let excluded = is_dir && is_excluded(&entry.file_name, &excludes);
+                let skip_hidden = if is_dir {
+                    skip_hidden_dirs
+                } else {
+                    skip_hidden_files
+                };
+                // Without a depth the only child is the root, which is
+                // scanned even when hidden.
+                let hidden = skip_hidden
+                    && depth.is_some()
+                    && (is_hidden_name(&entry.file_name) || has_hidden_attribute(entry));
+                // Mount points are the only place the device can change.
+                let foreign =
+                    is_dir && root_device.is_some() && device_id(&entry.path()) != root_device;
+                let ignored = !ignores.is_empty() && is_ignored(ignores, &entry.path(), is_dir);
+                !(excluded || hidden || foreign || ignored)
+            }
+            Err(_) => true,
+        });
+        if follow_links {
+            mark_link_loops(dir, children);
+        }
+    });
+
+    let root = root.to_path_buf();
+    walker.into_iter().flat_map(move |entry| match entry {
+        Ok(entry) if entry.client_state => {
+            std::iter::once(Err(ScanError::symlink_loop(entry.path()))).chain(None)
+        }
+        Ok(mut entry) => {
+            let path = entry.path();
+            // jwalk attaches failures to read a directory to its entry.
+            let read_error = entry
+                .read_children_error
+                .take()
+                .map(|err| Err(jwalk_error(&path, err)));
+            let walked = Ok(WalkEntry {
+                path,
+                file_type: entry.file_type(),
+            });
+            std::iter::once(walked).chain(read_error)
+        }
+        Err(err) => std::iter::once(Err(jwalk_error(&root, err))).chain(None),
+    })
+}
+
+fn walk_gitignore(
+    root: &Path,
+    opts: &ScanOptions,
+) -> impl Iterator<Item = Result<WalkEntry, ScanError>> {
+    let mut builder = WalkBuilder::new(root);
+    // ignore also checks the hidden attribute on Windows. When only files or
+    // only directories are skipped, the dot is checked below instead.
+    let skip_all_hidden = opts.skip_hidden_files && opts.skip_hidden_dirs;
+    builder
+        .hidden(skip_all_hidden)
+        .follow_links(opts.follow_links)
+        .git_ignore(true)
+        .git_global(true)
+        .git_exclude(true)
+        .ignore(true)
+        .same_file_system(opts.one_file_system)
+        .max_depth(opts.max_depth_for(root))
+        .add_custom_ignore_filename(IGNORE_FILE_NAME);
+    let global = build_ignore(root, &opts.ignore_files);
+    let skip_some_hidden = !skip_all_hidden && (opts.skip_hidden_files || opts.skip_hidden_dirs);
+    if !opts.excludes.is_empty() || global.is_some() || skip_some_hidden {
+        let excludes = opts.excludes.clone();
+        let (skip_hidden_files, skip_hidden_dirs) = (opts.skip_hidden_files, opts.skip_hidden_dirs);
+        builder.filter_entry(move |entry| {
+            let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+            let excluded = is_dir && is_excluded(entry.file_name(), &excludes);
+            let skip_hidden = if is_dir {
+                skip_hidden_dirs
+            } else {
+                skip_hidden_files
+            };
+            let hidden = skip_hidden && is_hidden_name(entry.file_name());
+            let ignored = global
+                .as_ref()
+                .is_some_and(|global| is_ignored(&[Arc::clone(global)], entry.path(), is_dir));
+            !(excluded || hidden || ignored)
+        });
+    }
+
+    let root = root.to_path_buf();
+    builder.build().filter_map(move |entry| match entry {
+        // Only stdin has no file type, and it is never walked.
+        Ok(entry) => entry.file_type().map(|file_type| {
+            Ok(WalkEntry {
+                path: entry.into_path(),
+                file_type,
+            })
+        }),
+        Err(err) => Some(Err(ignore_error(&root, err))),
+    })
+}
+
+/// Orders files by size so a min-heap can evict the smallest candidate.
+/// Among equal sizes the later path ranks lower, matching [`scan`]'s order,
+/// so which files make the cut does not depend on walk order.
+struct BySize(FileInfo);
+
+impl PartialEq for BySize {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for BySize {}
+
+impl PartialOrd for BySize {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BySize {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0
+            .size
+            .cmp(&other.0.size)
+            .then_with(|| other.0.path.cmp(&self.0.path))
+    }
+}
+
+/// Collects matched files, either all of them or only the `limit` largest.
+enum Collector {
+    All(Vec<FileInfo>),
+    Top {
+        limit: usize,
+        heap: BinaryHeap<Reverse<BySize>>,
+    },
+}
+
+impl Collector {
+    fn new(limit: Option<usize>) -> Self {
+        match limit {
+            Some(limit) => Collector::Top {
+                limit,
+                heap: BinaryHeap::with_capacity(limit + 1),
+            },
+            None => Collector::All(Vec::new()),
+        }
+    }
+
+    fn push(&mut self, file: FileInfo) {
+        match self {
+            Collector::All(files) => files.push(file),
+            Collector::Top { limit, heap } => {
+                let file = BySize(file);
+                if heap.len() < *limit {
+                    heap.push(Reverse(file));
+                } else if heap.peek().is_some_and(|Reverse(min)| file > *min) {
+                    heap.pop();
+                    heap.push(Reverse(file));
+                }
+            }
+        }
+    }
+
+    fn into_vec(self) -> Vec<FileInfo> {
+        match self {
+            Collector::All(files) => files,
+            Collector::Top { heap, .. } => heap.into_iter().map(|Reverse(f)| f.0).collect(),
+        }
+    }
+}
+
+/// Number of levels `path` lies below `root`; the root's children are at 1.
+fn depth_below(root: &Path, path: &Path) -> usize {
+    path.strip_prefix(root)
+        .map_or(0, |relative| relative.components().count())
+}
+
+fn add_to_ancestors(dir_sizes: &mut HashMap<PathBuf, u64>, root: &Path, path: &Path, size: u64) {
+    for dir in path.ancestors().skip(1) {
+        *dir_sizes.entry(dir.to_path_buf()).or_insert(0) += size;
+        if dir == root {
+            break;
+        }
+    }
+}
+
+/// Files already visited, shared across roots.
+#[derive(Default)]
+struct Visited {
+    /// Canonical path of every file when following links, so a file
+    /// reachable through several links counts once.
+    paths: HashSet<PathBuf>,
+    /// Canonical paths seen so far for each multiply linked inode.
+    inodes: HashMap<(u64, u64), Vec<PathBuf>>,
+    /// Canonical path of every matched file when scanning several roots,
+    /// so a file under overlapping roots counts once.
+    matched: HashSet<PathBuf>,
+}
+
+impl Visited {
+    /// Whether `path` is another hard link to an inode that was already
+    /// counted. Reaching the very same path again through overlapping roots
+    /// does not count; that is handled by [`Visited::matched`].
+    fn is_extra_link(&mut self, path: &Path, metadata: &fs::Metadata) -> bool {
+        let Some(key) = inode_key(metadata) else {
+            return false;
+        };
+        let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let links = self.inodes.entry(key).or_default();
+        if links.contains(&canonical) {
+            return false;
+        }
+        links.push(canonical);
+        links.len() > 1
+    }
+}
+
+#[cfg(unix)]
+fn allocated_size(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn allocated_size(metadata: &fs::Metadata) -> u64 {
+    metadata.len()
+}
+
+#[cfg(unix)]
+fn file_owner(metadata: &fs::Metadata) -> Option<Owner> {
+    use std::os::unix::fs::MetadataExt;
+    Some(Owner {
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+    })
+}
+
+#[cfg(not(unix))]
+fn file_owner(_: &fs::Metadata) -> Option<Owner> {
+    None
+}
+
+/// Device and inode of a file with several hard links, which all share the
+/// same key. Only available on Unix.
+#[cfg(unix)]
+pub fn inode_key(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+pub fn inode_key(_: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+fn scan_root(root: &Path, opts: &ScanOptions, visited: &mut Visited, result: &mut ScanResult) {
+    let mut files = Collector::new(opts.limit);
+    let mut dir_sizes: HashMap<PathBuf, u64> = HashMap::new();
+    let now = SystemTime::now();
+
+    for entry in walk_root(root, opts) {
+        if opts.stopped() {
+            result.interrupted = true;
+            break;
+        }
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                result.record_error(err);
+                continue;
+            }
+        };
+        if entry.file_type.is_dir() {
+            result.dirs_scanned += 1;
+            if let Some(ref progress) = opts.progress {
+                if let Ok(mut dir) = progress.dir.lock() {
+                    dir.clone_from(&entry.path);
+                }
+            }
+            if opts.find_empty_dirs
+                && fs::read_dir(&entry.path).is_ok_and(|mut children| children.next().is_none())
+            {
+                result.empty_dirs.push(entry.path);
+            }
+        } else if entry.file_type.is_file() {
+            if opts.follow_links {
+                let canonical =
+                    fs::canonicalize(&entry.path).unwrap_or_else(|_| entry.path.clone());
+                if !visited.paths.insert(canonical) {
+                    continue;
+                }
+            }
+            result.files_scanned += 1;
+            if let Some(ref progress) = opts.progress {
+                progress.files.fetch_add(1, Ordering::Relaxed);
+            }
+            if let Some(ref exts) = opts.extensions {
+                if !has_extension(&entry.path, exts) {
+                    continue;
+                }
+            }
+            if has_extension(&entry.path, &opts.excluded_extensions) {
+                continue;
+            }
+            if let Some(min_depth) = opts.min_depth {
+                if depth_below(root, &entry.path) < min_depth {
+                    continue;
+                }
+            }
+            if let Some(ref pattern) = opts.name_pattern {
+                if !matches_pattern(&entry.path, pattern, opts.match_path) {
+                    continue;
+                }
+            }
+            if !opts.globs_allow(&entry.path) {
+                continue;
+            }
+            let started = opts.profile.then(Instant::now);
+            let metadata = if opts.follow_links {
+                fs::metadata(&entry.path)
+            } else {
+                fs::symlink_metadata(&entry.path)
+            };
+            if let Some(started) = started {
+                result.metadata_time += started.elapsed();
+            }
+            let metadata = match metadata {
+                Ok(metadata) => metadata,
+                Err(err) => {
+                    result.record_error(ScanError::io(entry.path, &err));
+                    continue;
+                }
+            };
+            let size = if opts.disk_usage {
+                allocated_size(&metadata)
+            } else {
+                metadata.len()
+            };
+            result.bytes_scanned += size;
+            if let Some(ref progress) = opts.progress {
+                progress.bytes.fetch_add(size, Ordering::Relaxed);
+            }
+            if let Some(uid) = opts.owner_uid {
+                if file_owner(&metadata).is_none_or(|owner| owner.uid != uid) {
+                    continue;
+                }
+            }
+            if opts.filters_by_age() {
+                match metadata.modified() {
+                    Ok(modified) if opts.age_in_range(now, modified) => {}
+                    Ok(_) => continue,
+                    Err(_) => {
+                        result.mtime_skipped += 1;
+                        continue;
+                    }
+                }
+            }
+            if let Some(min_age) = opts.accessed_before {
+                let Ok(accessed) = metadata.accessed() else {
+                    result.atime_skipped += 1;
+                    continue;
+                };
+                result.atime_checked += 1;
+                if same_second(Some(accessed), metadata.modified().ok()) {
+                    result.atime_matches_mtime += 1;
+                }
+                if now.duration_since(accessed).unwrap_or(Duration::ZERO) < min_age {
+                    continue;
+                }
+            }
+            let extra_link = opts.count_links_once && visited.is_extra_link(&entry.path, &metadata);
+            // A file given as the root has no directory of its own to add to.
+            if opts.aggregate_dirs && !extra_link && entry.path != root {
+                add_to_ancestors(&mut dir_sizes, root, &entry.path, size);
+            }
+            if opts.size_in_range(size) {
+                // Checked before counting, as a limit may leave the earlier
+                // copy out of the files kept.
+                if opts.roots.len() > 1 && !opts.follow_links {
+                    let canonical =
+                        fs::canonicalize(&entry.path).unwrap_or_else(|_| entry.path.clone());
+                    if !visited.matched.insert(canonical) {
+                        result.overlapping += 1;
+                        continue;
+                    }
+                }
+                result.matched += 1;
+                result.matched_bytes += size;
+                if extra_link {
+                    result.links_collapsed += 1;
+                    result.linked_bytes += size;
+                }
+                let file = FileInfo {
+                    path: entry.path,
+                    size,
+                    modified: metadata.modified().ok(),
+                    owner: opts.read_owner.then(|| file_owner(&metadata)).flatten(),
+                    inode: inode_key(&metadata),
+                };
+                if let Some(OnMatch(ref on_match)) = opts.on_match {
+                    on_match(&file);
+                }
+                files.push(file);
+            }
+        }
+    }
+
+    result.files.extend(files.into_vec());
+    for (dir, size) in dir_sizes {
+        // Overlapping roots report the same subtree twice; keep one copy.
+        let total = result.dir_sizes.entry(dir).or_insert(0);
+        *total = (*total).max(size);
+    }
+}
+
+fn walk_root(
+    root: &Path,
+    opts: &ScanOptions,
+) -> Box<dyn Iterator<Item = Result<WalkEntry, ScanError>>> {
+    if opts.gitignore {
+        Box::new(walk_gitignore(root, opts))
+    } else {
+        Box::new(walk_jwalk(root, opts))
+    }
+}
+
+/// Counts the files [`scan`] would visit, as the total for a progress bar.
+///
+/// Gives up and returns `None` once `budget` has elapsed, so huge trees are
+/// not walked twice in full.
+pub fn count_files(opts: &ScanOptions, budget: Duration) -> Option<u64> {
+    let deadline = Instant::now() + budget;
+    let mut count = 0u64;
+    for root in &opts.roots {
+        for entry in walk_root(root, opts).flatten() {
+            if entry.file_type.is_file() {
+                count += 1;
+                if count.is_multiple_of(1024) && Instant::now() > deadline {
+                    return None;
+                }
+            }
+        }
+    }
+    Some(count)
+}
+
+/// Walks every root in `opts` and returns the matched files, largest first
+/// and by path among equal sizes.
+///
+/// Files reachable from more than one root are reported once.
+pub fn scan(opts: &ScanOptions) -> ScanResult {
+    let mut result = ScanResult::default();
+    let mut visited = Visited::default();
+    for root in &opts.roots {
+        scan_root(root, opts, &mut visited, &mut result);
+        if result.interrupted {
+            break;
+        }
+    }
+
+    result
+        .files
+        .sort_unstable_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    result.empty_dirs.sort_unstable();
+    result.empty_dirs.dedup();
+    if let Some(limit) = opts.limit {
+        result.files.truncate(limit);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a fresh directory under the system temp dir holding a
+    /// 100-byte file at each of `files`.
+    fn temp_tree(name: &str, files: &[&str]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("fatcat-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for file in files {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, [0u8; 100]).unwrap();
+        }
+        root
+    }
+
+    fn names(result: &ScanResult, root: &Path) -> Vec<String> {
+        let mut names: Vec<String> = result
+            .files
+            .iter()
+            .filter_map(|f| f.path.strip_prefix(root).ok())
+            .map(|p| p.to_string_lossy().replace('\\', "/"))
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn root_depths_apply_per_root() {
+        let files = ["1", "a/2", "a/b/3", "a/b/c/4"];
+        let shallow = temp_tree("depth-shallow", &files);
+        let deep = temp_tree("depth-deep", &files);
+        let opts = ScanOptions {
+            roots: vec![shallow.clone(), deep.clone()],
+            root_depths: HashMap::from([(shallow.clone(), 1), (deep.clone(), 3)]),
+            ..Default::default()
+        };
+        let result = scan(&opts);
+        assert_eq!(names(&result, &shallow), ["1"]);
+        assert_eq!(names(&result, &deep), ["1", "a/2", "a/b/3"]);
+
+        // Roots without their own limit fall back to `max_depth`.
+        let opts = ScanOptions {
+            root_depths: HashMap::from([(deep.clone(), 3)]),
+            max_depth: Some(2),
+            ..opts
+        };
+        let result = scan(&opts);
+        assert_eq!(names(&result, &shallow), ["1", "a/2"]);
+        assert_eq!(names(&result, &deep), ["1", "a/2", "a/b/3"]);
+
+        fs::remove_dir_all(shallow).unwrap();
+        fs::remove_dir_all(deep).unwrap();
+    }
+
+    #[test]
+    fn overlapping_roots_count_once_with_limit() {
+        let root = temp_tree("overlap", &[]);
+        fs::create_dir_all(root.join("sub")).unwrap();
+        for (name, size) in [("big", 300), ("sub/x", 200), ("sub/y", 100)] {
+            fs::write(root.join(name), vec![0u8; size]).unwrap();
+        }
+        let opts = ScanOptions {
+            roots: vec![root.clone(), root.join("sub")],
+            limit: Some(1),
+            ..Default::default()
+        };
+        let result = scan(&opts);
+        assert_eq!(result.matched, 3);
+        assert_eq!(result.matched_bytes, 600);
+        assert_eq!(result.overlapping, 2);
+        assert_eq!(names(&result, &root), ["big"]);
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -6,67 +6,459 @@
 // the Free Software Foundation, either version 3 of the License, or
 // (at your option) any later version.
 
-use chrono::Local;
+use chrono::{DateTime, Local};
 use colored::*;
+use fatcat::{
+    count_files, format_size_with, scan, FileInfo, OnMatch, Owner, Progress, ScanOptions,
+    ScanResult, Units,
+};
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use indicatif::{ProgressBar, ProgressStyle};
-use jwalk::WalkDir;
+use rayon::prelude::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
-use std::fs::File;
-use std::io::{BufWriter, Write};
-use std::path::PathBuf;
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::{Duration, Instant};
+use std::fmt::Display;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufWriter, IsTerminal, Write};
+use std::num::IntErrorKind;
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use terminal_size::{terminal_size, Width};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-#[derive(Debug, Clone)]
-struct FileInfo {
-    path: PathBuf,
-    size: u64,
+static SI_UNITS: AtomicBool = AtomicBool::new(false);
+
+/// Set by `--no-grouping` to print counts without thousands separators.
+static NO_GROUPING: AtomicBool = AtomicBool::new(false);
+
+fn units() -> Units {
+    if SI_UNITS.load(Ordering::Relaxed) {
+        Units::Si
+    } else {
+        Units::Binary
+    }
 }
 
+/// Formats a byte count in the unit convention chosen with `--si`.
 fn format_size(bytes: u64) -> String {
-    const KB: u64 = 1024;
-    const MB: u64 = KB * 1024;
-    const GB: u64 = MB * 1024;
-    const TB: u64 = GB * 1024;
-
-    if bytes >= TB {
-        format!("{:.2} TB", bytes as f64 / TB as f64)
-    } else if bytes >= GB {
-        format!("{:.2} GB", bytes as f64 / GB as f64)
-    } else if bytes >= MB {
-        format!("{:.2} MB", bytes as f64 / MB as f64)
-    } else if bytes >= KB {
-        format!("{:.2} KB", bytes as f64 / KB as f64)
+    format_size_with(bytes, units())
+}
+
+/// Formats a count with thousands separators, e.g. `1,234,567`, unless
+/// `--no-grouping` is given.
+fn format_count(count: u64) -> String {
+    let digits = count.to_string();
+    if NO_GROUPING.load(Ordering::Relaxed) {
+        return digits;
+    }
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// Formats `count` per second, e.g. `12.3k files/s`.
+fn format_rate(count: u64, elapsed: f64, unit: &str) -> String {
+    let rate = if elapsed > 0.0 {
+        count as f64 / elapsed
+    } else {
+        0.0
+    };
+    if rate >= 1_000_000.0 {
+        format!("{:.1}M {}/s", rate / 1_000_000.0, unit)
+    } else if rate >= 1_000.0 {
+        format!("{:.1}k {}/s", rate / 1_000.0, unit)
+    } else {
+        format!("{:.0} {}/s", rate, unit)
+    }
+}
+
+/// Formats bytes per second with `format_size`, e.g. `1.20 GB/s`.
+fn format_byte_rate(bytes: u64, elapsed: f64) -> String {
+    let rate = if elapsed > 0.0 {
+        bytes as f64 / elapsed
+    } else {
+        0.0
+    };
+    format!("{}/s", format_size(rate as u64))
+}
+
+fn format_date(time: Option<SystemTime>) -> String {
+    match time {
+        Some(t) => DateTime::<Local>::from(t).format("%Y-%m-%d").to_string(),
+        None => "----".to_string(),
+    }
+}
+
+/// Marks the output of a scan cut short by Ctrl-C.
+const INTERRUPTED_NOTE: &str = "interrupted, results are partial";
+
+#[derive(Serialize)]
+struct Report<'a> {
+    version: &'static str,
+    /// The command line fatcat was run with, quoted for a POSIX shell.
+    command: String,
+    roots: Vec<String>,
+    min_size: u64,
+    max_size: Option<u64>,
+    files_scanned: u64,
+    dirs_scanned: u64,
+    files_found: u64,
+    /// Combined size of all matched files, not only the listed ones.
+    matched_bytes: u64,
+    bytes_scanned: u64,
+    mtime_skipped: u64,
+    atime_skipped: u64,
+    links_collapsed: u64,
+    linked_bytes: u64,
+    errors: u64,
+    elapsed: f64,
+    /// Whether Ctrl-C ended the scan early, so the results are partial.
+    interrupted: bool,
+    sort: SortKey,
+    reverse: bool,
+    units: Units,
+    disk_usage: bool,
+    /// Space on the filesystem of each root, where it could be read.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    disks: Vec<DiskSpace>,
+    #[serde(skip)]
+    buckets: &'a [u64],
+    /// Add a raw byte count column to the text log, see `--size-bytes`.
+    #[serde(skip)]
+    size_bytes: bool,
+    /// Indent JSON output, see `--json-pretty`.
+    #[serde(skip)]
+    json_pretty: bool,
+    #[serde(serialize_with = "serialize_files")]
+    files: &'a [FileInfo],
+}
+
+/// Serializes each file with a `size_human` field in the units of `--si`.
+fn serialize_files<S: serde::Serializer>(
+    files: &&[FileInfo],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct Entry<'a> {
+        #[serde(flatten)]
+        file: &'a FileInfo,
+        size_human: String,
+    }
+    serializer.collect_seq(files.iter().map(|file| Entry {
+        file,
+        size_human: format_size(file.size),
+    }))
+}
+
+impl Report<'_> {
+    /// Combined size of the matched files, counting hard links once when
+    /// `--count-links-once` is given.
+    fn total_size(&self) -> u64 {
+        let listed: u64 = self.files.iter().map(|f| f.size).sum();
+        listed.saturating_sub(self.linked_bytes)
+    }
+
+    /// Combined size of all matched files, including unlisted ones, counting
+    /// hard links once when `--count-links-once` is given.
+    fn matched_total(&self) -> u64 {
+        self.matched_bytes.saturating_sub(self.linked_bytes)
+    }
+
+    /// Share of `size` in [`Report::matched_total`], in percent.
+    fn percent_of_matched(&self, size: u64) -> f64 {
+        percent_of(size, self.matched_total())
+    }
+}
+
+fn percent_of(size: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        size as f64 / total as f64 * 100.0
+    }
+}
+
+fn parse_size(value: &str) -> Result<u64, String> {
+    let invalid = || format!("Invalid size value: '{}'", value.yellow());
+    let trimmed = value.trim();
+    let split = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(trimmed.len());
+    let (number, suffix) = trimmed.split_at(split);
+
+    let multiplier: u64 = match suffix.to_ascii_uppercase().as_str() {
+        "B" => 1,
+        "K" | "KB" => 1024,
+        "" | "M" | "MB" => 1024 * 1024,
+        "G" | "GB" => 1024 * 1024 * 1024,
+        "T" | "TB" => 1024 * 1024 * 1024 * 1024,
+        _ => return Err(invalid()),
+    };
+
+    let bytes = if number.contains('.') {
+        let number: f64 = number.parse().map_err(|_| invalid())?;
+        let bytes = (number * multiplier as f64).round();
+        (bytes < u64::MAX as f64).then_some(bytes as u64)
+    } else {
+        match number.parse::<u64>() {
+            Ok(n) => n.checked_mul(multiplier),
+            Err(e) if *e.kind() == IntErrorKind::PosOverflow => None,
+            Err(_) => return Err(invalid()),
+        }
+    };
+    bytes.ok_or_else(|| format!("Size value too large: '{}'", value.yellow()))
+}
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+const YEAR: u64 = 365 * DAY;
+
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid duration: '{}'", value.yellow());
+    let trimmed = value.trim();
+    let split = trimmed
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+
+    let seconds: u64 = match unit.to_ascii_lowercase().as_str() {
+        "s" => 1,
+        "m" => MINUTE,
+        "h" => HOUR,
+        "d" => DAY,
+        "w" => WEEK,
+        "y" => YEAR,
+        _ => return Err(invalid()),
+    };
+    let number: u64 = number.parse().map_err(|_| invalid())?;
+    number
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(invalid)
+}
+
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    for (unit, label) in [
+        (YEAR, "y"),
+        (WEEK, "w"),
+        (DAY, "d"),
+        (HOUR, "h"),
+        (MINUTE, "m"),
+    ] {
+        if secs >= unit && secs.is_multiple_of(unit) {
+            return format!("{}{}", secs / unit, label);
+        }
+    }
+    format!("{}s", secs)
+}
+
+/// Formats a scan duration as e.g. `1h 2m 34s` once it exceeds a minute, and
+/// as seconds with two decimals followed by `short_unit` below that.
+fn format_elapsed(secs: f64, short_unit: &str) -> String {
+    if secs < MINUTE as f64 {
+        return format!("{:.2}{}", secs, short_unit);
+    }
+    let total = secs.round() as u64;
+    let (hours, minutes, seconds) = (total / HOUR, total % HOUR / MINUTE, total % MINUTE);
+    if hours > 0 {
+        format!("{}h {}m {}s", hours, minutes, seconds)
     } else {
-        format!("{} B", bytes)
+        format!("{}m {}s", minutes, seconds)
     }
 }
 
+/// Removes terminal escape sequences: CSI (`ESC [` ... final byte in `@`-`~`),
+/// OSC (`ESC ]` ... BEL or `ESC \`) and two-byte `ESC x` sequences.
 fn strip_ansi(s: &str) -> String {
     let mut result = String::with_capacity(s.len());
     let mut chars = s.chars().peekable();
     while let Some(c) = chars.next() {
-        if c == '\x1b' {
-            while let Some(&next) = chars.peek() {
-                chars.next();
-                if next == 'm' {
-                    break;
+        if c != '\x1b' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('[') => {
+                for next in chars.by_ref() {
+                    if ('@'..='~').contains(&next) {
+                        break;
+                    }
                 }
             }
-        } else {
-            result.push(c);
+            Some(']') => {
+                while let Some(next) = chars.next() {
+                    if next == '\x07' {
+                        break;
+                    }
+                    if next == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => {}
         }
     }
     result
 }
 
 fn visible_width(s: &str) -> usize {
-    strip_ansi(s).chars().count()
+    UnicodeWidthStr::width(strip_ansi(s).as_str())
+}
+
+struct BoxGlyphs {
+    top_left: &'static str,
+    top_right: &'static str,
+    bottom_left: &'static str,
+    bottom_right: &'static str,
+    horizontal: &'static str,
+    vertical: &'static str,
+    bar: &'static str,
+    tree_branch: &'static str,
+    tree_last: &'static str,
+    tree_pipe: &'static str,
+    ellipsis: &'static str,
+}
+
+const UNICODE_BOX: BoxGlyphs = BoxGlyphs {
+    top_left: "╭",
+    top_right: "╮",
+    bottom_left: "╰",
+    bottom_right: "╯",
+    horizontal: "─",
+    vertical: "│",
+    bar: "█",
+    tree_branch: "├── ",
+    tree_last: "└── ",
+    tree_pipe: "│   ",
+    ellipsis: "…",
+};
+
+const ASCII_BOX: BoxGlyphs = BoxGlyphs {
+    top_left: "+",
+    top_right: "+",
+    bottom_left: "+",
+    bottom_right: "+",
+    horizontal: "-",
+    vertical: "|",
+    bar: "#",
+    tree_branch: "|-- ",
+    tree_last: "`-- ",
+    tree_pipe: "|   ",
+    ellipsis: "...",
+};
+
+static ASCII_BOXES: AtomicBool = AtomicBool::new(false);
+/// Print boxes as a title line followed by their content, for non-TTY output.
+static PLAIN_BOXES: AtomicBool = AtomicBool::new(false);
+
+/// Widest box line that fits the terminal, or 0 when lines are never
+/// shortened, e.g. with `--full-paths`.
+static MAX_LINE_WIDTH: AtomicUsize = AtomicUsize::new(0);
+
+/// Longer boxes are printed like `PLAIN_BOXES`, e.g. for `--top 0`.
+const MAX_BOX_LINES: usize = 1000;
+
+fn box_glyphs() -> &'static BoxGlyphs {
+    if ASCII_BOXES.load(Ordering::Relaxed) {
+        &ASCII_BOX
+    } else {
+        &UNICODE_BOX
+    }
+}
+
+/// Fewest columns a shortened path keeps, ellipsis excluded.
+const MIN_PATH_WIDTH: usize = 16;
+
+/// Shortens `line` to `max_width` columns by replacing part of it with an
+/// ellipsis. When the line contains a path, the columns before it are kept
+/// and the path loses its middle, so its file name stays visible. Escape
+/// sequences are dropped from lines that are shortened.
+fn truncate_middle(line: &str, max_width: usize) -> String {
+    let plain = strip_ansi(line);
+    if UnicodeWidthStr::width(plain.as_str()) <= max_width {
+        return line.to_string();
+    }
+    let ellipsis = box_glyphs().ellipsis;
+    let budget = max_width.saturating_sub(visible_width(ellipsis));
+    let take = |chars: &mut dyn Iterator<Item = char>, limit: usize| {
+        let mut width = 0;
+        let mut taken = Vec::new();
+        for c in chars {
+            width += c.width().unwrap_or(0);
+            if width > limit {
+                break;
+            }
+            taken.push(c);
+        }
+        taken
+    };
+    // Columns before the first separator, e.g. rank and size; kept whole
+    // when that leaves a useful part of the path.
+    let prefix = plain
+        .find(['/', '\\'])
+        .map(|i| UnicodeWidthStr::width(&plain[..i]))
+        .filter(|&width| width + MIN_PATH_WIDTH <= budget)
+        .unwrap_or(0);
+    let head_width = prefix + (budget - prefix) / 2;
+    let head = take(&mut plain.chars(), head_width);
+    let mut tail = take(&mut plain.chars().rev(), budget - head_width);
+    tail.reverse();
+    format!(
+        "{}{}{}",
+        head.iter().collect::<String>(),
+        ellipsis,
+        tail.iter().collect::<String>()
+    )
 }
 
 fn print_box(title: &str, content: &[String], color: Color) {
+    for line in box_lines(title, content, color) {
+        println!("{}", line);
+    }
+}
+
+/// Lays out `content` in a box titled `title`, one string per output line.
+fn box_lines(title: &str, content: &[String], color: Color) -> Vec<String> {
+    if PLAIN_BOXES.load(Ordering::Relaxed) || content.len() > MAX_BOX_LINES {
+        let mut lines = vec![title.color(color).bold().to_string()];
+        lines.extend(content.iter().cloned());
+        return lines;
+    }
+
+    let max_width = MAX_LINE_WIDTH.load(Ordering::Relaxed);
+    let truncated: Vec<String>;
+    let content = if max_width > 0 {
+        truncated = content
+            .iter()
+            .map(|line| truncate_middle(line, max_width))
+            .collect();
+        &truncated
+    } else {
+        content
+    };
+
+    let g = box_glyphs();
     let content_width = content
         .iter()
         .map(|s| visible_width(s))
@@ -74,35 +466,38 @@ fn print_box(title: &str, content: &[String], color: Color) {
         .unwrap_or(40)
         .max(40);
     let title_str = format!(" {} ", title);
-    let title_len = title_str.chars().count();
+    let title_len = visible_width(&title_str);
     let box_width = content_width + 2;
 
     let top_right_padding = box_width.saturating_sub(title_len + 1);
-    println!(
-        "{}{}{}{}",
-        "╭─".color(color),
+    let mut lines = Vec::with_capacity(content.len() + 2);
+    lines.push(format!(
+        "{}{}{}{}{}",
+        g.top_left.color(color),
+        g.horizontal.color(color),
         title_str.color(color).bold(),
-        "─".repeat(top_right_padding).color(color),
-        "╮".color(color)
-    );
+        g.horizontal.repeat(top_right_padding).color(color),
+        g.top_right.color(color)
+    ));
 
     for line in content {
         let padding = content_width - visible_width(line);
-        println!(
+        lines.push(format!(
             "{} {}{} {}",
-            "│".color(color),
+            g.vertical.color(color),
             line,
             " ".repeat(padding),
-            "│".color(color)
-        );
+            g.vertical.color(color)
+        ));
     }
 
-    println!(
+    lines.push(format!(
         "{}{}{}",
-        "╰".color(color),
-        "─".repeat(box_width).color(color),
-        "╯".color(color)
-    );
+        g.bottom_left.color(color),
+        g.horizontal.repeat(box_width).color(color),
+        g.bottom_right.color(color)
+    ));
+    lines
 }
 
 fn print_usage() {
@@ -110,7 +505,7 @@ fn print_usage() {
     println!(
         "Usage: {} {}",
         "fatcat".cyan().bold(),
-        "[PATH] [OPTIONS]".dimmed()
+        "[PATH...] [OPTIONS]".dimmed()
     );
     println!("Try '{}' for help.", "fatcat --help".green());
     println!();
@@ -127,42 +522,328 @@ fn print_help() {
     println!(
         "Usage: {} {}",
         "fatcat".cyan().bold(),
-        "[PATH] [OPTIONS]".dimmed()
+        "[PATH...] [OPTIONS]".dimmed()
     );
     println!();
 
-    let options = vec![
-        format!(
-            "{}  {}     Minimum file size in MB (default: 100)",
-            "-s, --size".green(),
-            "<MB>".dimmed()
+    let table = [
+        (
+            "-s, --size",
+            "<SIZE>",
+            "Minimum file size (default: 100M; bare number = MB)",
+        ),
+        ("    --max-size", "<SIZE>", "Maximum file size"),
+        ("    --include-zero", "", "Also include zero-byte files"),
+        (
+            "    --older-than",
+            "<AGE>",
+            "Only files modified before AGE (e.g. 90d)",
+        ),
+        (
+            "    --accessed-before",
+            "<AGE>",
+            "Only files not accessed within AGE (e.g. 1y)",
+        ),
+        (
+            "    --newer-than",
+            "<AGE>",
+            "Only files modified within AGE (e.g. 6h)",
+        ),
+        (
+            "-o, --output",
+            "<FILE>",
+            "Save results to log file, - for stdout",
+        ),
+        (
+            "-f, --format",
+            "<FMT>",
+            "Log format: text, csv, json, yaml, md, html",
+        ),
+        (
+            "    --append",
+            "",
+            "Append to the log file instead of replacing it",
+        ),
+        (
+            "    --cache",
+            "<FILE>",
+            "Show changes since the scan saved in FILE",
+        ),
+        (
+            "-t, --top",
+            "<N>",
+            "Show top N files, 0 for all (default: 20)",
+        ),
+        (
+            "    --sort",
+            "<KEY>",
+            "Sort by size, name or date (default: size)",
+        ),
+        ("-r, --reverse", "", "Reverse the sort order"),
+        (
+            "    --smallest",
+            "",
+            "Show the N smallest matched files instead",
+        ),
+        (
+            "    --newest",
+            "",
+            "Show the N most recently modified matches",
+        ),
+        (
+            "    --oldest",
+            "",
+            "Show the N least recently modified matches",
+        ),
+        ("    --absolute", "", "Show canonical absolute paths"),
+        (
+            "    --relative",
+            "",
+            "Show paths relative to their scan root",
+        ),
+        ("    --stdin", "", "Also scan paths read from stdin"),
+        (
+            "    --fail-if-found",
+            "[=LIMIT]",
+            "Exit with code 2 if more than LIMIT match",
+        ),
+        (
+            "    --watch",
+            "<SECS>",
+            "Rescan every SECS seconds until Ctrl-C",
+        ),
+        (
+            "    --ext",
+            "<LIST>",
+            "Only include these extensions (e.g. mp4,mkv)",
+        ),
+        (
+            "    --exclude-ext",
+            "<LIST>",
+            "Skip these extensions (e.g. iso,zip)",
+        ),
+        (
+            "    --match",
+            "<REGEX>",
+            "Only include file names matching REGEX",
+        ),
+        (
+            "    --match-path",
+            "",
+            "Match --match against the full path instead",
+        ),
+        (
+            "    --glob",
+            "<GLOB>",
+            "Only include paths matching GLOB (repeatable)",
+        ),
+        (
+            "    --exclude-glob",
+            "<GLOB>",
+            "Skip paths matching GLOB (repeatable)",
+        ),
+        (
+            "    --exclude",
+            "<NAME>",
+            "Skip directories with this name (repeatable)",
+        ),
+        (
+            "    --gitignore",
+            "",
+            "Skip files ignored by .gitignore/.ignore",
+        ),
+        (
+            "    --ignore-file",
+            "<PATH>",
+            "Skip paths matching patterns in PATH",
+        ),
+        (
+            "-x, --one-file-system",
+            "",
+            "Do not cross filesystem boundaries",
         ),
-        format!(
-            "{}  {}   Save results to log file",
-            "-o, --output".green(),
-            "<FILE>".dimmed()
+        (
+            "    --disk-usage",
+            "",
+            "Measure allocated disk space, like du",
         ),
-        format!(
-            "{}  {}      Show top N files (default: 20)",
-            "-t, --top".green(),
-            "<N>".dimmed()
+        (
+            "    --count-links-once",
+            "",
+            "Count hard-linked files' size only once",
         ),
-        format!(
-            "{}            Show detailed statistics",
-            "-v, --verbose".green()
+        ("    --no-hidden", "", "Skip hidden files and directories"),
+        ("    --skip-hidden-files", "", "Skip hidden files only"),
+        (
+            "    --skip-hidden-dirs",
+            "",
+            "Skip hidden directories and their contents",
         ),
-        format!(
-            "{}               Show this help message",
-            "-h, --help".green()
+        ("    --owner", "", "Show the owner and group of each file"),
+        ("    --user", "<NAME>", "Only include files owned by NAME"),
+        ("-L, --follow-links", "", "Follow symbolic links"),
+        (
+            "    --depth",
+            "<N>",
+            "Descend at most N levels below each root",
         ),
+        (
+            "    --min-depth",
+            "<N>",
+            "Skip files less than N levels below a root",
+        ),
+        (
+            "-j, --threads",
+            "<N>",
+            "Number of scan threads (default: auto)",
+        ),
+        ("    --dirs", "", "Rank directories by total size instead"),
+        (
+            "    --min-dir-size",
+            "<SIZE>",
+            "Smallest directory total for --dirs",
+        ),
+        ("    --tree", "", "Show the top N files in a directory tree"),
+        (
+            "    --empty",
+            "",
+            "List empty files and directories instead",
+        ),
+        ("    --by-ext", "", "Rank extensions by total size instead"),
+        (
+            "    --by-category",
+            "",
+            "Rank file types like Video by total size",
+        ),
+        ("    --duplicates", "", "Find files with identical content"),
+        ("    --duplicates-summary", "", "Only show duplicate totals"),
+        (
+            "    --buckets",
+            "<LIST>",
+            "Size distribution thresholds (e.g. 1G,10G)",
+        ),
+        ("-v, --verbose", "", "Show detailed statistics"),
+        (
+            "    --profile",
+            "",
+            "Show how long each phase of the run took",
+        ),
+        ("    --summary", "", "Show statistics only, no file list"),
+        ("    --si", "", "Show sizes in powers of 1000, not 1024"),
+        (
+            "    --no-grouping",
+            "",
+            "Print counts without thousands separators",
+        ),
+        ("    --histogram", "", "Show a file size histogram"),
+        ("-q, --quiet", "", "Print only size and path, one per line"),
+        (
+            "    --plain",
+            "",
+            "Print ranked results as aligned plain columns",
+        ),
+        (
+            "    --size-bytes",
+            "",
+            "Add exact byte counts to text and plain output",
+        ),
+        ("-0, --print0", "", "Print all matched paths NUL-separated"),
+        ("    --json", "", "Print results as JSON"),
+        ("    --json-pretty", "", "Like --json, but indented"),
+        (
+            "    --total-only",
+            "",
+            "Print only the total size of the matches",
+        ),
+        (
+            "    --stats-only-json",
+            "",
+            "Print only totals and buckets as JSON",
+        ),
+        (
+            "    --ndjson",
+            "",
+            "Stream matches as JSON lines while scanning",
+        ),
+        ("    --yaml", "", "Print results as YAML"),
+        ("    --markdown", "", "Print results as a Markdown report"),
+        ("    --html", "", "Print results as an HTML page"),
+        ("-i, --interactive", "", "Offer to delete each listed file"),
+        (
+            "    --trash",
+            "",
+            "Move deleted files to the trash (default)",
+        ),
+        ("    --force", "", "Delete files permanently instead"),
+        (
+            "    --config",
+            "<FILE>",
+            "Read defaults from FILE (default: ~/.config/fatcat/config.toml)",
+        ),
+        (
+            "    --no-config",
+            "",
+            "Ignore the config files found by default",
+        ),
+        (
+            "    --progress",
+            "",
+            "Count files first to show a progress bar",
+        ),
+        (
+            "    --no-progress",
+            "",
+            "Disable the progress bar and spinner",
+        ),
+        (
+            "    --pager",
+            "",
+            "Always page output through $PAGER or less",
+        ),
+        ("    --no-pager", "", "Never page output"),
+        ("    --no-color", "", "Disable colored output"),
+        ("    --color", "<WHEN>", "Use colors: auto, always or never"),
+        ("    --ascii", "", "Draw boxes with ASCII characters only"),
+        (
+            "    --full-paths",
+            "",
+            "Never shorten lines to fit the terminal",
+        ),
+        ("-V, --version", "", "Show version information"),
+        ("-h, --help", "", "Show this help message"),
     ];
+    let flag_width = table
+        .iter()
+        .map(|(flag, arg, _)| flag.len() + arg.len() + 2)
+        .max()
+        .unwrap_or(0);
+    let options: Vec<String> = table
+        .iter()
+        .map(|(flag, arg, desc)| {
+            let padding = flag_width - (flag.len() + arg.len() + 2);
+            format!(
+                "{}  {}{}   {}",
+                flag.green(),
+                arg.dimmed(),
+                " ".repeat(padding),
+                desc
+            )
+        })
+        .collect();
     print_box("Options", &options, Color::Blue);
 
     println!();
     let examples = vec![
         "fatcat".to_string(),
         "fatcat /home".to_string(),
+        "fatcat /home /var /opt -s 500".to_string(),
         "fatcat ./downloads -s 500".to_string(),
+        "fatcat /home -s 2G".to_string(),
+        "fatcat /home -s 100M --max-size 1G".to_string(),
+        "fatcat /media --ext mp4,mkv,mov".to_string(),
+        "fatcat ~ --exclude node_modules,.git,target".to_string(),
+        "fatcat ~ --older-than 90d".to_string(),
+        "fatcat ~ --dirs -t 10".to_string(),
         "fatcat -v -o result.log".to_string(),
     ];
     print_box("Examples", &examples, Color::Cyan);
@@ -177,194 +858,3256 @@ fn print_error(msg: &str) {
     println!();
 }
 
-fn scan_directory(
-    root: &str,
-    min_size_bytes: u64,
-    file_count: &AtomicU64,
-    dir_count: &AtomicU64,
-) -> Vec<FileInfo> {
-    let spinner = ProgressBar::new_spinner();
+fn start_spinner(message: &'static str, visible: bool) -> ProgressBar {
+    let spinner = if visible {
+        ProgressBar::new_spinner()
+    } else {
+        ProgressBar::hidden()
+    };
     spinner.set_style(
         ProgressStyle::default_spinner()
             .template("  {spinner:.cyan} {msg}")
             .unwrap()
-            .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏"),
+            .tick_chars(if ASCII_BOXES.load(Ordering::Relaxed) {
+                "|/-\\ "
+            } else {
+                "⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏"
+            }),
     );
-    spinner.set_message("Scanning...");
+    spinner.set_message(message);
     spinner.enable_steady_tick(Duration::from_millis(80));
+    spinner
+}
 
-    let mut files: Vec<FileInfo> = Vec::new();
+/// How long the pre-count for the progress bar may take before falling back
+/// to the spinner.
+const PRECOUNT_BUDGET: Duration = Duration::from_secs(2);
 
-    for entry in WalkDir::new(root)
-        .skip_hidden(false)
-        .follow_links(false)
-        .into_iter()
-        .filter_map(|e| e.ok())
-    {
-        let file_type = entry.file_type();
-        if file_type.is_dir() {
-            dir_count.fetch_add(1, Ordering::Relaxed);
-        } else if file_type.is_file() {
-            file_count.fetch_add(1, Ordering::Relaxed);
-            if let Ok(metadata) = entry.metadata() {
-                let size = metadata.len();
-                if size >= min_size_bytes {
-                    files.push(FileInfo {
-                        path: entry.path(),
-                        size,
-                    });
+/// Scans with a progress bar when the files can be counted quickly enough,
+/// and with a spinner otherwise. Also returns how long the count took.
+fn scan_with_progress(opts: &ScanOptions) -> (ScanResult, Duration) {
+    let counting = Instant::now();
+    let spinner = start_spinner("Counting files...", true);
+    let total = count_files(opts, PRECOUNT_BUDGET);
+    spinner.finish_and_clear();
+    let counting = counting.elapsed();
+
+    let progress = Arc::new(Progress::default());
+    let opts = ScanOptions {
+        progress: Some(Arc::clone(&progress)),
+        ..opts.clone()
+    };
+
+    let Some(total) = total else {
+        let spinner = start_spinner("Scanning...", true);
+        // Room for the spinner and the "Scanning" label.
+        let width = terminal_size().map_or(80, |(Width(width), _)| width as usize);
+        let width = width.saturating_sub(14).max(MIN_PATH_WIDTH);
+        let done = AtomicBool::new(false);
+        let result = thread::scope(|s| {
+            s.spawn(|| {
+                // Slower than the tick, so the path does not flicker.
+                while !done.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(250));
+                    let dir = match progress.dir.lock() {
+                        Ok(dir) if !dir.as_os_str().is_empty() => dir.display().to_string(),
+                        _ => continue,
+                    };
+                    spinner.set_message(format!("Scanning {}", truncate_middle(&dir, width)));
                 }
+            });
+            let result = scan(&opts);
+            done.store(true, Ordering::Relaxed);
+            result
+        });
+        spinner.finish_and_clear();
+        return (result, counting);
+    };
+
+    let bar = ProgressBar::new(total);
+    bar.set_style(
+        ProgressStyle::default_bar()
+            .template("  {bar:30.cyan/blue} {percent:>3}%  {msg}  ETA {eta}")
+            .unwrap()
+            .progress_chars(if ASCII_BOXES.load(Ordering::Relaxed) {
+                "#> "
+            } else {
+                "━╸ "
+            }),
+    );
+    let done = AtomicBool::new(false);
+    let result = thread::scope(|s| {
+        s.spawn(|| {
+            while !done.load(Ordering::Relaxed) {
+                bar.set_position(progress.files.load(Ordering::Relaxed));
+                bar.set_message(format_size(progress.bytes.load(Ordering::Relaxed)));
+                thread::sleep(Duration::from_millis(100));
             }
+        });
+        let result = scan(&opts);
+        done.store(true, Ordering::Relaxed);
+        result
+    });
+    bar.finish_and_clear();
+    (result, counting)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum SortKey {
+    Size,
+    Name,
+    Date,
+}
+
+impl SortKey {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value.to_ascii_lowercase().as_str() {
+            "size" => Ok(SortKey::Size),
+            "name" => Ok(SortKey::Name),
+            "date" => Ok(SortKey::Date),
+            _ => Err(format!("Unknown sort key: '{}'", value.yellow())),
         }
     }
 
-    spinner.finish_and_clear();
+    fn label(self) -> &'static str {
+        match self {
+            SortKey::Size => "size",
+            SortKey::Name => "name",
+            SortKey::Date => "date",
+        }
+    }
+}
 
-    files.sort_unstable_by(|a, b| b.size.cmp(&a.size));
-    files
+/// When `--fail-if-found` makes fatcat exit with code 2.
+#[derive(Clone, Copy)]
+enum FailThreshold {
+    /// More than this many files matched.
+    Count(u64),
+    /// The matched files add up to more than this many bytes.
+    Size(u64),
 }
 
-fn write_log(
-    files: &[FileInfo],
-    log_path: &str,
-    scan_root: &str,
-    min_size: u64,
-    total_files: u64,
-    total_dirs: u64,
-    elapsed: f64,
-) -> std::io::Result<()> {
-    let file = File::create(log_path)?;
-    let mut w = BufWriter::new(file);
+impl FailThreshold {
+    /// A plain number is a file count; a number with a unit is a total size.
+    fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().parse() {
+            Ok(count) => Ok(FailThreshold::Count(count)),
+            Err(_) => parse_size(value).map(FailThreshold::Size),
+        }
+    }
 
-    let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
+    fn exceeded(self, report: &Report) -> bool {
+        match self {
+            FailThreshold::Count(count) => report.files_found > count,
+            FailThreshold::Size(bytes) => report.matched_total() > bytes,
+        }
+    }
+}
 
-    writeln!(w, "FATCAT - Scan Report")?;
-    writeln!(w, "====================")?;
-    writeln!(w)?;
-    writeln!(w, "Timestamp       : {}", timestamp)?;
-    writeln!(w, "Scan Target     : {}", scan_root)?;
-    writeln!(w, "Min Size        : {}", format_size(min_size))?;
-    writeln!(w, "Files Scanned   : {}", total_files)?;
-    writeln!(w, "Dirs Scanned    : {}", total_dirs)?;
-    writeln!(w, "Files Found     : {}", files.len())?;
-    writeln!(w, "Elapsed Time    : {:.2} sec", elapsed)?;
-    writeln!(w)?;
+/// How paths are shown, see `--absolute` and `--relative`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PathStyle {
+    AsGiven,
+    Absolute,
+    Relative,
+}
 
-    let total_size: u64 = files.iter().map(|f| f.size).sum();
-    writeln!(w, "Total Size      : {}", format_size(total_size))?;
-    writeln!(w)?;
+/// Rewrites `path` for display. Paths that cannot be canonicalized or lie
+/// outside every root are returned unchanged.
+fn styled_path(path: &Path, style: PathStyle, roots: &[PathBuf]) -> PathBuf {
+    match style {
+        PathStyle::AsGiven => path.to_path_buf(),
+        PathStyle::Absolute => fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()),
+        PathStyle::Relative => roots
+            .iter()
+            .filter_map(|root| path.strip_prefix(root).ok())
+            .min_by_key(|rel| rel.components().count())
+            .map(|rel| {
+                if rel.as_os_str().is_empty() {
+                    // The root itself, which keeps its name if it is a file.
+                    match path.file_name() {
+                        Some(name) if path.is_file() => PathBuf::from(name),
+                        _ => PathBuf::from("."),
+                    }
+                } else {
+                    rel.to_path_buf()
+                }
+            })
+            .unwrap_or_else(|| path.to_path_buf()),
+    }
+}
 
-    let gb_files = files.iter().filter(|f| f.size >= 1_073_741_824).count();
-    let mb_500_files = files
-        .iter()
-        .filter(|f| f.size >= 524_288_000 && f.size < 1_073_741_824)
-        .count();
-    let mb_100_files = files
-        .iter()
-        .filter(|f| f.size >= 104_857_600 && f.size < 524_288_000)
-        .count();
+/// Sorts largest, alphabetically first or newest first; `reverse` flips it.
+/// Ties are broken by path, so the order does not depend on the walk.
+/// Unknown modification times sort last in either direction.
+fn sort_files(files: &mut [FileInfo], key: SortKey, reverse: bool) {
+    match key {
+        SortKey::Size => {
+            files.sort_unstable_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)))
+        }
+        SortKey::Name => files.sort_unstable_by(|a, b| {
+            a.path
+                .file_name()
+                .cmp(&b.path.file_name())
+                .then_with(|| a.path.cmp(&b.path))
+        }),
+        SortKey::Date => files.sort_unstable_by(|a, b| {
+            b.modified
+                .cmp(&a.modified)
+                .then_with(|| a.path.cmp(&b.path))
+        }),
+    }
+    if reverse {
+        files.reverse();
+    }
+    if key == SortKey::Date {
+        // Stable, so the order among known times is kept.
+        files.sort_by_key(|f| f.modified.is_none());
+    }
+}
 
-    writeln!(w, "Size Distribution")?;
-    writeln!(w, "-----------------")?;
-    writeln!(w, ">= 1 GB         : {} files", gb_files)?;
-    writeln!(w, "500 MB - 1 GB   : {} files", mb_500_files)?;
-    writeln!(w, "100 MB - 500 MB : {} files", mb_100_files)?;
-    writeln!(w)?;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Text,
+    Csv,
+    Json,
+    Yaml,
+    Markdown,
+    Html,
+}
 
-    writeln!(w, "All Files (sorted by size)")?;
-    writeln!(w, "--------------------------")?;
-    for (i, file) in files.iter().enumerate() {
-        writeln!(
-            w,
-            "{:>5}. {:>12}  {}",
-            i + 1,
-            format_size(file.size),
-            file.path.display()
-        )?;
+impl OutputFormat {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value.to_ascii_lowercase().as_str() {
+            "text" | "txt" => Ok(OutputFormat::Text),
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "yaml" | "yml" => Ok(OutputFormat::Yaml),
+            "markdown" | "md" => Ok(OutputFormat::Markdown),
+            "html" | "htm" => Ok(OutputFormat::Html),
+            _ => Err(format!("Unknown format: '{}'", value.yellow())),
+        }
     }
 
-    w.flush()?;
-    Ok(())
+    /// File extension for logs named automatically by `write_log`.
+    fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Text => "log",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+            OutputFormat::Yaml => "yaml",
+            OutputFormat::Markdown => "md",
+            OutputFormat::Html => "html",
+        }
+    }
 }
 
-struct Config {
-    path: String,
-    min_size_mb: u64,
-    output: Option<String>,
-    top_n: usize,
-    verbose: bool,
+struct DuplicateGroup {
+    size: u64,
+    paths: Vec<PathBuf>,
 }
 
-fn parse_args() -> Result<Config, String> {
-    let args: Vec<String> = env::args().collect();
+impl DuplicateGroup {
+    fn wasted(&self) -> u64 {
+        self.size * (self.paths.len() as u64 - 1)
+    }
+}
 
-    let mut config = Config {
-        path: String::from("./"),
-        min_size_mb: 100,
-        output: None,
-        top_n: 20,
-        verbose: false,
+fn hash_file(path: &Path) -> std::io::Result<blake3::Hash> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(File::open(path)?)?;
+    Ok(hasher.finalize())
+}
+
+/// Groups `files` by content. Only the matched files are passed in, so the
+/// size, extension, name and glob filters already limit what gets hashed.
+fn find_duplicates(files: &[FileInfo]) -> Vec<DuplicateGroup> {
+    let mut by_size: HashMap<u64, Vec<&FileInfo>> = HashMap::new();
+    for file in files.iter().filter(|f| f.size > 0) {
+        by_size.entry(file.size).or_default().push(file);
+    }
+
+    // Further hard links to a file share its data, so deleting them frees
+    // nothing; only the first path to each inode is compared.
+    let mut inodes = HashSet::new();
+    let candidates: Vec<&FileInfo> = by_size
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .flatten()
+        .filter(|f| f.inode.is_none_or(|key| inodes.insert(key)))
+        .collect();
+
+    let hashed: Vec<(u64, blake3::Hash, &PathBuf)> = candidates
+        .par_iter()
+        .filter_map(|f| hash_file(&f.path).ok().map(|hash| (f.size, hash, &f.path)))
+        .collect();
+
+    let mut by_hash: HashMap<(u64, blake3::Hash), Vec<PathBuf>> = HashMap::new();
+    for (size, hash, path) in hashed {
+        by_hash.entry((size, hash)).or_default().push(path.clone());
+    }
+
+    let mut groups: Vec<DuplicateGroup> = by_hash
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|((size, _), mut paths)| {
+            paths.sort();
+            DuplicateGroup { size, paths }
+        })
+        .collect();
+    groups.sort_unstable_by(|a, b| {
+        b.wasted()
+            .cmp(&a.wasted())
+            .then_with(|| a.paths.cmp(&b.paths))
+    });
+    groups
+}
+
+/// Writes the report to `log_path`, replacing the file unless `append` is set,
+/// and returns the path written to. When `log_path` is a directory, a new
+/// timestamped file is created inside it, and `-` writes to stdout.
+///
+/// Appended text reports are separated by a rule, appended CSV skips the
+/// header row when the file already has content, and appended JSON adds one
+/// line per run.
+fn write_log(
+    report: &Report,
+    log_path: &str,
+    format: OutputFormat,
+    append: bool,
+) -> std::io::Result<PathBuf> {
+    if log_path == "-" {
+        let stdout = std::io::stdout();
+        let mut w = BufWriter::new(stdout.lock());
+        write_report(&mut w, report, format, false, false)?;
+        w.flush()?;
+        return Ok(PathBuf::from(log_path));
+    }
+    let mut path = PathBuf::from(log_path);
+    if path.is_dir() {
+        path.push(format!(
+            "fatcat-{}.{}",
+            Local::now().format("%Y%m%d-%H%M%S"),
+            format.extension()
+        ));
+    }
+    let file = if append {
+        OpenOptions::new().create(true).append(true).open(&path)?
+    } else {
+        File::create(&path)?
     };
+    let has_content = append && file.metadata()?.len() > 0;
+    let mut w = BufWriter::new(file);
+    write_report(&mut w, report, format, append, has_content)?;
+    w.flush()?;
+    Ok(path)
+}
 
-    let mut i = 1;
-    while i < args.len() {
-        match args[i].as_str() {
-            "-h" | "--help" => {
-                print_help();
-                std::process::exit(0);
+/// Writes `report` in `format`. `has_content` tells whether it is appended
+/// after an earlier report.
+fn write_report<W: Write>(
+    w: &mut W,
+    report: &Report,
+    format: OutputFormat,
+    append: bool,
+    has_content: bool,
+) -> std::io::Result<()> {
+    match format {
+        OutputFormat::Text => {
+            if has_content {
+                writeln!(w)?;
+                writeln!(w, "{}", "=".repeat(72))?;
+                writeln!(w)?;
             }
-            "-s" | "--size" => {
-                i += 1;
-                if i >= args.len() {
-                    return Err(format!(
-                        "Option '{}' requires an argument.",
-                        "-s, --size".yellow()
-                    ));
+            write_text_report(w, report)?
+        }
+        OutputFormat::Csv => write_csv_report(w, report.files, !has_content)?,
+        // Appended runs must stay one line each.
+        OutputFormat::Json => write_json(w, report, report.json_pretty && !append)?,
+        OutputFormat::Yaml => {
+            if has_content {
+                writeln!(w, "---")?;
+            }
+            write_yaml(w, report)?;
+        }
+        OutputFormat::Markdown => {
+            if has_content {
+                writeln!(w)?;
+                writeln!(w, "---")?;
+                writeln!(w)?;
+            }
+            write_markdown_report(w, report)?
+        }
+        OutputFormat::Html => write_html_report(w, report)?,
+    }
+    Ok(())
+}
+
+fn write_text_report<W: Write>(w: &mut W, report: &Report) -> std::io::Result<()> {
+    let files = report.files;
+    let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
+
+    writeln!(w, "FATCAT - Scan Report")?;
+    writeln!(w, "====================")?;
+    writeln!(w)?;
+    writeln!(w, "Timestamp       : {}", timestamp)?;
+    writeln!(w, "Version         : fatcat {}", report.version)?;
+    writeln!(w, "Command         : {}", report.command)?;
+    writeln!(w, "Scan Target     : {}", report.roots.join(", "))?;
+    writeln!(
+        w,
+        "Units           : {}",
+        match report.units {
+            Units::Binary => "binary (1 KB = 1024 B)",
+            Units::Si => "SI (1 kB = 1000 B)",
+        }
+    )?;
+    writeln!(
+        w,
+        "Size Measure    : {}",
+        if report.disk_usage {
+            "disk usage (allocated blocks)"
+        } else {
+            "apparent size"
+        }
+    )?;
+    writeln!(w, "Min Size        : {}", format_size(report.min_size))?;
+    if let Some(max) = report.max_size {
+        writeln!(w, "Max Size        : {}", format_size(max))?;
+    }
+    writeln!(
+        w,
+        "Files Scanned   : {}",
+        format_count(report.files_scanned)
+    )?;
+    writeln!(w, "Dirs Scanned    : {}", format_count(report.dirs_scanned))?;
+    writeln!(w, "Files Found     : {}", format_count(report.files_found))?;
+    if report.errors > 0 {
+        writeln!(w, "Unreadable      : {}", format_count(report.errors))?;
+    }
+    writeln!(
+        w,
+        "Elapsed Time    : {}",
+        format_elapsed(report.elapsed, " sec")
+    )?;
+    if report.interrupted {
+        writeln!(w, "Status          : {}", INTERRUPTED_NOTE)?;
+    }
+    writeln!(w)?;
+    writeln!(w, "Total Size      : {}", format_size(report.total_size()))?;
+    for disk in &report.disks {
+        writeln!(
+            w,
+            "Disk Space      : {}",
+            disk.describe(report.disks.len() > 1)
+        )?;
+    }
+    if report.links_collapsed > 0 {
+        writeln!(
+            w,
+            "Hard Links      : {} counted once",
+            format_count(report.links_collapsed)
+        )?;
+    }
+    writeln!(w)?;
+
+    writeln!(w, "Size Distribution")?;
+    writeln!(w, "-----------------")?;
+    for bucket in size_distribution(files, report.buckets) {
+        writeln!(
+            w,
+            "{:<15} : {} files",
+            bucket.label(),
+            format_count(bucket.files as u64)
+        )?;
+    }
+    writeln!(w)?;
+
+    let heading = format!(
+        "All Files (sorted by {}{})",
+        report.sort.label(),
+        if report.reverse { ", reversed" } else { "" }
+    );
+    writeln!(w, "{}", heading)?;
+    writeln!(w, "{}", "-".repeat(heading.len()))?;
+    let owners = owner_cells(files);
+    let bytes_width = files.iter().map(|f| f.size.to_string().len()).max();
+    for (i, (file, owner)) in files.iter().zip(&owners).enumerate() {
+        let bytes = match bytes_width {
+            Some(width) if report.size_bytes => format!("{:>width$}  ", file.size),
+            _ => String::new(),
+        };
+        writeln!(
+            w,
+            "{:>5}. {}{:>12}  {:>5.1}%  {:<10}  {}{}",
+            i + 1,
+            bytes,
+            format_size(file.size),
+            report.percent_of_matched(file.size),
+            format_date(file.modified),
+            owner,
+            file.path.display()
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Quotes `arg` for a POSIX shell when it contains anything but plain
+/// word characters, so a logged command can be pasted back into a shell.
+fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_alphanumeric() || "-_./=:,+%@".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Escapes `|` so a value cannot break out of its Markdown table cell.
+fn markdown_cell(value: &str) -> String {
+    value.replace('|', "\\|")
+}
+
+fn write_markdown_report<W: Write>(w: &mut W, report: &Report) -> std::io::Result<()> {
+    let files = report.files;
+    let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
+
+    writeln!(w, "# fatcat Scan Report")?;
+    writeln!(w)?;
+    writeln!(w, "## Summary")?;
+    writeln!(w)?;
+    writeln!(w, "- **Timestamp:** {}", timestamp)?;
+    writeln!(w, "- **Version:** fatcat {}", report.version)?;
+    writeln!(w, "- **Command:** `{}`", report.command)?;
+    writeln!(w, "- **Scan target:** {}", report.roots.join(", "))?;
+    writeln!(w, "- **Min size:** {}", format_size(report.min_size))?;
+    if let Some(max) = report.max_size {
+        writeln!(w, "- **Max size:** {}", format_size(max))?;
+    }
+    writeln!(
+        w,
+        "- **Files scanned:** {}",
+        format_count(report.files_scanned)
+    )?;
+    writeln!(
+        w,
+        "- **Dirs scanned:** {}",
+        format_count(report.dirs_scanned)
+    )?;
+    writeln!(w, "- **Files found:** {}", format_count(report.files_found))?;
+    writeln!(w, "- **Total size:** {}", format_size(report.total_size()))?;
+    writeln!(
+        w,
+        "- **Elapsed time:** {}",
+        format_elapsed(report.elapsed, " sec")
+    )?;
+    if report.interrupted {
+        writeln!(w, "- **Status:** {}", INTERRUPTED_NOTE)?;
+    }
+    writeln!(w)?;
+
+    writeln!(
+        w,
+        "## Files (sorted by {}{})",
+        report.sort.label(),
+        if report.reverse { ", reversed" } else { "" }
+    )?;
+    writeln!(w)?;
+    writeln!(w, "| Rank | Size | Share | Path |")?;
+    writeln!(w, "|-----:|-----:|------:|------|")?;
+    for (i, file) in files.iter().enumerate() {
+        writeln!(
+            w,
+            "| {} | {} | {:.1}% | {} |",
+            i + 1,
+            format_size(file.size),
+            report.percent_of_matched(file.size),
+            markdown_cell(&file.path.to_string_lossy())
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Escapes text for use in HTML element content and attribute values.
+fn html_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const HTML_STYLE: &str = "body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; }
+th, td { padding: 0.3em 0.8em; border-bottom: 1px solid #ddd; }
+th { cursor: pointer; background: #f4f4f4; user-select: none; }
+td.num { text-align: right; white-space: nowrap; }
+dt { font-weight: bold; float: left; clear: left; width: 9em; }
+dd { margin-left: 10em; }";
+
+/// Sorts the file table by the clicked column, toggling the direction on
+/// repeated clicks. Cells are compared by their `data-value`.
+const HTML_SCRIPT: &str = "document.querySelectorAll('th').forEach((th, column) => {
+  th.addEventListener('click', () => {
+    const body = th.closest('table').tBodies[0];
+    const numeric = th.dataset.type === 'number';
+    const ascending = th.dataset.order !== 'asc';
+    th.dataset.order = ascending ? 'asc' : 'desc';
+    const value = row => row.cells[column].dataset.value;
+    const rows = Array.from(body.rows).sort((a, b) => {
+      const order = numeric ? value(a) - value(b) : value(a).localeCompare(value(b));
+      return ascending ? order : -order;
+    });
+    rows.forEach(row => body.appendChild(row));
+  });
+});";
+
+/// Writes a self-contained HTML page with the summary and a file table that
+/// sorts when a column header is clicked.
+fn write_html_report<W: Write>(w: &mut W, report: &Report) -> std::io::Result<()> {
+    let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
+
+    writeln!(w, "<!DOCTYPE html>")?;
+    writeln!(w, "<html lang=\"en\">")?;
+    writeln!(w, "<head>")?;
+    writeln!(w, "<meta charset=\"utf-8\">")?;
+    writeln!(w, "<title>fatcat Scan Report</title>")?;
+    writeln!(w, "<style>\n{}\n</style>", HTML_STYLE)?;
+    writeln!(w, "</head>")?;
+    writeln!(w, "<body>")?;
+    writeln!(w, "<h1>fatcat Scan Report</h1>")?;
+    writeln!(w, "<h2>Summary</h2>")?;
+    writeln!(w, "<dl>")?;
+    let mut summary = vec![
+        ("Timestamp", timestamp.to_string()),
+        ("Version", format!("fatcat {}", report.version)),
+        ("Command", report.command.clone()),
+        ("Scan target", report.roots.join(", ")),
+        ("Min size", format_size(report.min_size)),
+    ];
+    if let Some(max) = report.max_size {
+        summary.push(("Max size", format_size(max)));
+    }
+    summary.extend([
+        ("Files scanned", format_count(report.files_scanned)),
+        ("Dirs scanned", format_count(report.dirs_scanned)),
+        ("Files found", format_count(report.files_found)),
+        ("Total size", format_size(report.total_size())),
+        ("Elapsed time", format_elapsed(report.elapsed, " sec")),
+    ]);
+    if report.interrupted {
+        summary.push(("Status", INTERRUPTED_NOTE.to_string()));
+    }
+    for (term, value) in summary {
+        writeln!(w, "<dt>{}</dt><dd>{}</dd>", term, html_escape(&value))?;
+    }
+    writeln!(w, "</dl>")?;
+
+    writeln!(
+        w,
+        "<h2>Files (sorted by {}{})</h2>",
+        report.sort.label(),
+        if report.reverse { ", reversed" } else { "" }
+    )?;
+    writeln!(w, "<table>")?;
+    writeln!(
+        w,
+        "<thead><tr><th data-type=\"number\">Rank</th><th data-type=\"number\">Size</th><th data-type=\"number\">Share</th><th>Path</th></tr></thead>"
+    )?;
+    writeln!(w, "<tbody>")?;
+    for (i, file) in report.files.iter().enumerate() {
+        let path = html_escape(&file.path.to_string_lossy());
+        writeln!(
+            w,
+            "<tr><td class=\"num\" data-value=\"{rank}\">{rank}</td><td class=\"num\" data-value=\"{}\">{}</td><td class=\"num\" data-value=\"{}\">{:.1}%</td><td data-value=\"{path}\">{path}</td></tr>",
+            file.size,
+            format_size(file.size),
+            file.size,
+            report.percent_of_matched(file.size),
+            rank = i + 1,
+            path = path
+        )?;
+    }
+    writeln!(w, "</tbody>")?;
+    writeln!(w, "</table>")?;
+    writeln!(w, "<script>\n{}\n</script>", HTML_SCRIPT)?;
+    writeln!(w, "</body>")?;
+    writeln!(w, "</html>")?;
+    Ok(())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn write_csv_report<W: Write>(w: &mut W, files: &[FileInfo], header: bool) -> std::io::Result<()> {
+    if header {
+        writeln!(w, "rank,size_bytes,size_human,path")?;
+    }
+    for (i, file) in files.iter().enumerate() {
+        writeln!(
+            w,
+            "{},{},{},{}",
+            i + 1,
+            file.size,
+            csv_field(&format_size(file.size)),
+            csv_field(&file.path.to_string_lossy())
+        )?;
+    }
+    Ok(())
+}
+
+/// Middle value of non-empty ascending `sizes`, averaging the two middle
+/// values when the count is even.
+fn median(sizes: &[u64]) -> u64 {
+    let mid = sizes.len() / 2;
+    if sizes.len().is_multiple_of(2) {
+        sizes[mid - 1] + (sizes[mid] - sizes[mid - 1]) / 2
+    } else {
+        sizes[mid]
+    }
+}
+
+/// Space on the filesystem holding a scan root, and how much of it the
+/// matched files under that root take up.
+#[derive(Serialize)]
+struct DiskSpace {
+    root: String,
+    total: u64,
+    /// Space available to unprivileged users.
+    free: u64,
+    used: u64,
+    matched: u64,
+}
+
+impl DiskSpace {
+    /// Text for the statistics and the log, naming the root only if there
+    /// are several.
+    fn describe(&self, with_root: bool) -> String {
+        let mut text = format!(
+            "{} free of {}, matched {:.1}% of used",
+            format_size(self.free),
+            format_size(self.total),
+            percent_of(self.matched, self.used)
+        );
+        if with_root {
+            text.push_str(&format!(" ({})", self.root));
+        }
+        text
+    }
+}
+
+/// Reads the filesystem space of every root. `matched_bytes` is the combined
+/// size of all matches, which for several roots is split up by `files`.
+fn disk_spaces(roots: &[PathBuf], files: &[FileInfo], matched_bytes: u64) -> Vec<DiskSpace> {
+    roots
+        .iter()
+        .filter_map(|root| {
+            let (total, free, used) = filesystem_space(root)?;
+            let matched = if roots.len() == 1 {
+                matched_bytes
+            } else {
+                files
+                    .iter()
+                    .filter(|f| f.path.starts_with(root))
+                    .map(|f| f.size)
+                    .sum()
+            };
+            Some(DiskSpace {
+                root: root.display().to_string(),
+                total,
+                free,
+                used,
+                matched,
+            })
+        })
+        .collect()
+}
+
+/// Total, free and used bytes of the filesystem holding `path`. Only
+/// supported on Unix.
+#[cfg(unix)]
+// The statvfs field types differ between platforms.
+#[allow(clippy::useless_conversion)]
+fn filesystem_space(path: &Path) -> Option<(u64, u64, u64)> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    // SAFETY: statvfs only writes into the zeroed struct we pass.
+    let stat = unsafe {
+        let mut stat: libc::statvfs = std::mem::zeroed();
+        if libc::statvfs(path.as_ptr(), &mut stat) != 0 {
+            return None;
+        }
+        stat
+    };
+    let block = u64::from(stat.f_frsize);
+    let blocks = u64::from(stat.f_blocks);
+    Some((
+        blocks * block,
+        u64::from(stat.f_bavail) * block,
+        blocks.saturating_sub(u64::from(stat.f_bfree)) * block,
+    ))
+}
+
+#[cfg(not(unix))]
+fn filesystem_space(_: &Path) -> Option<(u64, u64, u64)> {
+    None
+}
+
+/// Formats the duration of a phase for `--profile`, in milliseconds below a
+/// second.
+fn format_phase(duration: Duration) -> String {
+    let secs = duration.as_secs_f64();
+    if secs < 1.0 {
+        format!("{:.1}ms", secs * 1000.0)
+    } else {
+        format_elapsed(secs, "s")
+    }
+}
+
+/// Prints how long each of `phases` took and its share of `total`, in a box
+/// or as plain lines on stderr when the output is not decorated.
+fn print_profile(phases: &[(&str, Duration)], total: Duration, decorated: bool) {
+    let mut lines: Vec<String> = phases
+        .iter()
+        .map(|(phase, duration)| {
+            format!(
+                "{:<15} : {:>9}  {:>5.1}%",
+                phase,
+                format_phase(*duration),
+                percent_of(duration.as_nanos() as u64, total.as_nanos() as u64)
+            )
+        })
+        .collect();
+    lines.push(format!("{:<15} : {:>9}", "Total", format_phase(total)));
+    if decorated {
+        print_box("Profile", &lines, Color::Magenta);
+        println!();
+    } else {
+        for line in lines {
+            eprintln!("{}", line);
+        }
+    }
+}
+
+fn print_statistics(report: &Report, config: &Config, result: &ScanResult) {
+    let files = report.files;
+    let mut stats = Vec::new();
+    if config.scan.roots.len() > 1 {
+        stats.push(format!("Roots scanned   : {}", config.scan.roots.len()));
+        if result.overlapping > 0 {
+            stats.push(format!(
+                "Overlapping     : {} files listed once",
+                format_count(result.overlapping)
+            ));
+        }
+    }
+    stats.push(format!(
+        "Dirs scanned    : {}",
+        format_count(report.dirs_scanned)
+    ));
+    stats.push(format!(
+        "Bytes scanned   : {}",
+        format_size(report.bytes_scanned)
+    ));
+    stats.push(format!(
+        "Throughput      : {}, {}",
+        format_rate(report.files_scanned, report.elapsed, "files"),
+        format_byte_rate(report.bytes_scanned, report.elapsed)
+    ));
+    if let Some(max) = config.scan.max_size {
+        stats.push(format!(
+            "Size range      : {} - {}",
+            format_size(config.scan.min_size),
+            format_size(max)
+        ));
+    }
+    if config.scan.aggregate_dirs {
+        stats.push(format!(
+            "Min dir size    : {}",
+            format_size(config.dir_size_minimum())
+        ));
+    }
+    if let Some(ref exts) = config.scan.extensions {
+        stats.push(format!("Extensions      : {}", exts.join(", ")));
+    }
+    if !config.scan.excluded_extensions.is_empty() {
+        stats.push(format!(
+            "Skipped exts    : {}",
+            config.scan.excluded_extensions.join(", ")
+        ));
+    }
+    if let (Some(user), Some(_)) = (&config.user, config.scan.owner_uid) {
+        stats.push(format!("Owned by        : {}", user));
+    }
+    if let Some(ref pattern) = config.scan.name_pattern {
+        let target = if config.scan.match_path {
+            "path"
+        } else {
+            "name"
+        };
+        stats.push(format!("Match {:<9} : {}", target, pattern));
+    }
+    if !config.globs.is_empty() {
+        stats.push(format!("Include globs   : {}", config.globs.join(", ")));
+    }
+    if !config.exclude_globs.is_empty() {
+        stats.push(format!(
+            "Exclude globs   : {}",
+            config.exclude_globs.join(", ")
+        ));
+    }
+    if let Some(depth) = config.scan.min_depth {
+        stats.push(format!("Min depth       : {}", depth));
+    }
+    if let Some(depth) = config.scan.max_depth {
+        stats.push(format!("Max depth       : {}", depth));
+    }
+    if !config.scan.root_depths.is_empty() {
+        let depths: Vec<String> = config
+            .scan
+            .roots
+            .iter()
+            .filter_map(|root| {
+                let depth = config.scan.root_depths.get(root)?;
+                Some(format!("{}:{}", root.display(), depth))
+            })
+            .collect();
+        stats.push(format!("Root depths     : {}", depths.join(", ")));
+    }
+    if let Some(window) = config.age_window() {
+        stats.push(format!("Age window      : {}", window));
+        if report.mtime_skipped > 0 {
+            stats.push(format!(
+                "Unknown mtime   : {} files skipped",
+                format_count(report.mtime_skipped)
+            ));
+        }
+    }
+    if let Some(age) = config.scan.accessed_before {
+        stats.push(format!("Not accessed in : {}", format_age(age)));
+        if report.atime_skipped > 0 {
+            stats.push(format!(
+                "Unknown atime   : {} files skipped",
+                format_count(report.atime_skipped)
+            ));
+        }
+    }
+    if config.scan.count_links_once {
+        stats.push(format!(
+            "Hard links      : {} collapsed ({})",
+            format_count(report.links_collapsed),
+            format_size(report.linked_bytes)
+        ));
+    }
+    if report.errors > 0 {
+        stats.push(format!(
+            "Unreadable      : {} entries",
+            format_count(report.errors)
+        ));
+        let error_samples = &result.error_samples;
+        for error in error_samples {
+            stats.push(format!("  {} ({})", error.path.display(), error.reason));
+        }
+        if report.errors > error_samples.len() as u64 {
+            stats.push(format!(
+                "  ... and {} more",
+                format_count(report.errors - error_samples.len() as u64)
+            ));
+        }
+    }
+    stats.push(format!(
+        "Total size      : {}",
+        format_size(report.total_size())
+    ));
+    for disk in &report.disks {
+        stats.push(format!(
+            "Disk space      : {}",
+            disk.describe(report.disks.len() > 1)
+        ));
+    }
+    if !files.is_empty() {
+        let mut sizes: Vec<u64> = files.iter().map(|f| f.size).collect();
+        sizes.sort_unstable();
+        let mean = sizes.iter().sum::<u64>() / sizes.len() as u64;
+        stats.extend([
+            format!("Mean size       : {}", format_size(mean)),
+            format!("Median size     : {}", format_size(median(&sizes))),
+            format!("Smallest        : {}", format_size(sizes[0])),
+            format!("Largest         : {}", format_size(sizes[sizes.len() - 1])),
+        ]);
+    }
+    for bucket in size_distribution(files, &config.buckets) {
+        stats.push(format!(
+            "{:<15} : {} files",
+            bucket.label(),
+            format_count(bucket.files as u64)
+        ));
+    }
+    print_box("Statistics", &stats, Color::Magenta);
+    println!();
+}
+
+const MB: u64 = 1024 * 1024;
+
+/// Size distribution thresholds used without `--buckets`.
+const DEFAULT_BUCKETS: [u64; 3] = [100 * MB, 500 * MB, 1024 * MB];
+
+/// Matched files whose size is at least `min` and, if given, below `max`.
+#[derive(Serialize)]
+struct SizeBucket {
+    min: u64,
+    max: Option<u64>,
+    files: usize,
+}
+
+impl SizeBucket {
+    fn label(&self) -> String {
+        // Thresholds are parsed in binary units, so label them that way too.
+        let short = |bytes: u64| format_size_with(bytes, Units::Binary).replace(".00", "");
+        match self.max {
+            Some(max) => format!("{} - {}", short(self.min), short(max)),
+            None => format!(">= {}", short(self.min)),
+        }
+    }
+}
+
+/// Counts files per size range between consecutive ascending `thresholds`,
+/// largest range first. Files below the smallest threshold are not counted.
+fn size_distribution(files: &[FileInfo], thresholds: &[u64]) -> Vec<SizeBucket> {
+    let mut rows = Vec::with_capacity(thresholds.len());
+    for (i, &min) in thresholds.iter().enumerate().rev() {
+        let max = thresholds.get(i + 1).copied();
+        rows.push(SizeBucket {
+            min,
+            max,
+            files: files
+                .iter()
+                .filter(|f| f.size >= min && max.is_none_or(|max| f.size < max))
+                .count(),
+        });
+    }
+    rows
+}
+
+/// Longest histogram bar, matching the minimum content width of `print_box`.
+const HISTOGRAM_WIDTH: usize = 40;
+
+/// Counts files per power-of-two size bucket. Bucket 0 holds everything
+/// below 1 MB and bucket `i` holds sizes in `[2^(i-1), 2^i)` MB.
+fn size_histogram(files: &[FileInfo]) -> Vec<u64> {
+    let mut buckets: Vec<u64> = Vec::new();
+    for file in files {
+        let index = match file.size / MB {
+            0 => 0,
+            mb => mb.ilog2() as usize + 1,
+        };
+        if buckets.len() <= index {
+            buckets.resize(index + 1, 0);
+        }
+        buckets[index] += 1;
+    }
+    buckets
+}
+
+fn bucket_label(index: usize) -> String {
+    // Bucket bounds are powers of two, so they are always labelled in binary
+    // units, where the decimals are zero.
+    let short = |bytes: u64| format_size_with(bytes, Units::Binary).replace(".00", "");
+    match index {
+        0 => format!("< {}", short(MB)),
+        i => format!("{} - {}", short(MB << (i - 1)), short(MB << i)),
+    }
+}
+
+fn print_histogram(files: &[FileInfo]) {
+    let buckets = size_histogram(files);
+    let first = buckets.iter().position(|&n| n > 0).unwrap_or(0);
+    let max_count = buckets.iter().copied().max().unwrap_or(0).max(1);
+    let labels: Vec<String> = (first..buckets.len()).map(bucket_label).collect();
+    let label_width = labels.iter().map(|l| l.len()).max().unwrap_or(0);
+    let count_width = max_count.to_string().len();
+    let bar = box_glyphs().bar;
+
+    let mut rows = Vec::with_capacity(labels.len());
+    for (label, &count) in labels.iter().zip(&buckets[first..]) {
+        let length = (count as usize * HISTOGRAM_WIDTH).div_ceil(max_count as usize);
+        rows.push(format!(
+            "{:>label_width$}  {:>count_width$}  {}",
+            label,
+            count,
+            bar.repeat(length).cyan()
+        ));
+    }
+    if rows.is_empty() {
+        rows.push("No files found matching criteria.".to_string());
+    }
+    print_box("Size Histogram", &rows, Color::Magenta);
+    println!();
+}
+
+#[cfg(unix)]
+thread_local! {
+    /// User and group names already looked up by [`owner_label`], as listings
+    /// tend to repeat the same few owners.
+    static USER_NAMES: std::cell::RefCell<HashMap<u32, Option<String>>> = Default::default();
+    static GROUP_NAMES: std::cell::RefCell<HashMap<u32, Option<String>>> = Default::default();
+}
+
+/// Runs one of the reentrant `getpw*_r`/`getgr*_r` lookups, growing the
+/// buffer until the entry fits, and passes the entry found to `read`.
+#[cfg(unix)]
+fn lookup_account<T, R>(
+    lookup: impl Fn(*mut T, *mut libc::c_char, libc::size_t, *mut *mut T) -> libc::c_int,
+    read: impl FnOnce(&T) -> R,
+) -> Option<R> {
+    let mut buf: Vec<libc::c_char> = vec![0; 1024];
+    loop {
+        // SAFETY: the entry is a plain C struct that the lookup fills in; the
+        // strings it points to live in `buf` until `read` returns.
+        let mut entry: T = unsafe { std::mem::zeroed() };
+        let mut found: *mut T = std::ptr::null_mut();
+        match lookup(&mut entry, buf.as_mut_ptr(), buf.len(), &mut found) {
+            libc::ERANGE if buf.len() < 1 << 20 => buf.resize(buf.len() * 2, 0),
+            0 if !found.is_null() => return Some(read(&entry)),
+            _ => return None,
+        }
+    }
+}
+
+/// Converts a name field of a looked up account entry.
+#[cfg(unix)]
+fn account_name(name: *const libc::c_char) -> String {
+    // SAFETY: `lookup_account` only hands out entries that were found, whose
+    // names are NUL-terminated strings in its buffer.
+    unsafe { std::ffi::CStr::from_ptr(name) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Shows `owner` as `user:group`, keeping the numeric id of any account that
+/// cannot be resolved.
+#[cfg(unix)]
+fn owner_label(owner: Owner) -> String {
+    let user = USER_NAMES.with(|names| {
+        names
+            .borrow_mut()
+            .entry(owner.uid)
+            .or_insert_with(|| {
+                lookup_account(
+                    // SAFETY: the pointers come from `lookup_account`.
+                    |entry, buf, len, found| unsafe {
+                        libc::getpwuid_r(owner.uid, entry, buf, len, found)
+                    },
+                    |entry: &libc::passwd| account_name(entry.pw_name),
+                )
+            })
+            .clone()
+    });
+    let group = GROUP_NAMES.with(|names| {
+        names
+            .borrow_mut()
+            .entry(owner.gid)
+            .or_insert_with(|| {
+                lookup_account(
+                    // SAFETY: the pointers come from `lookup_account`.
+                    |entry, buf, len, found| unsafe {
+                        libc::getgrgid_r(owner.gid, entry, buf, len, found)
+                    },
+                    |entry: &libc::group| account_name(entry.gr_name),
+                )
+            })
+            .clone()
+    });
+    format!(
+        "{}:{}",
+        user.unwrap_or_else(|| owner.uid.to_string()),
+        group.unwrap_or_else(|| owner.gid.to_string())
+    )
+}
+
+/// Looks up the id of the account `user`, which may also be given as a
+/// number. Accounts cannot be looked up on other platforms than Unix.
+#[cfg(unix)]
+fn user_id(user: &str) -> Result<Option<u32>, String> {
+    if let Ok(uid) = user.parse() {
+        return Ok(Some(uid));
+    }
+    let unknown = || format!("Unknown user: '{}'", user.yellow());
+    let name = std::ffi::CString::new(user).map_err(|_| unknown())?;
+    lookup_account(
+        // SAFETY: the pointers come from `lookup_account`.
+        |entry, buf, len, found| unsafe { libc::getpwnam_r(name.as_ptr(), entry, buf, len, found) },
+        |entry: &libc::passwd| Some(entry.pw_uid),
+    )
+    .ok_or_else(unknown)
+}
+
+#[cfg(not(unix))]
+fn user_id(_: &str) -> Result<Option<u32>, String> {
+    Ok(None)
+}
+
+#[cfg(not(unix))]
+fn owner_label(owner: Owner) -> String {
+    format!("{}:{}", owner.uid, owner.gid)
+}
+
+/// Owner column for each of `files`, padded and followed by a gap, or empty
+/// strings when no owners were recorded.
+fn owner_cells(files: &[FileInfo]) -> Vec<String> {
+    let labels: Vec<String> = files
+        .iter()
+        .map(|f| f.owner.map(owner_label).unwrap_or_default())
+        .collect();
+    let width = labels.iter().map(|l| l.width()).max().unwrap_or(0);
+    if width == 0 {
+        return labels;
+    }
+    labels
+        .into_iter()
+        .map(|label| format!("{}{}  ", label, " ".repeat(width - label.width())))
+        .collect()
+}
+
+/// Lists the first `top_n` files with their share of `total`, the combined
+/// size of all matched files. `label` starts the box title, e.g. "Top".
+fn print_top_files(files: &[FileInfo], top_n: usize, total: u64, label: &str) {
+    if files.is_empty() {
+        let content = vec!["No files found matching criteria.".to_string()];
+        print_box("Result", &content, Color::Yellow);
+        println!();
+        return;
+    }
+
+    let display_count = std::cmp::min(top_n, files.len());
+    let shown = &files[..display_count];
+    let mut file_list: Vec<String> = Vec::with_capacity(display_count);
+    for (i, (file, owner)) in shown.iter().zip(owner_cells(shown)).enumerate() {
+        file_list.push(format!(
+            "{:>3}. {:>10}  {:>5.1}%  {:<10}  {}{}",
+            i + 1,
+            format_size(file.size),
+            percent_of(file.size, total),
+            format_date(file.modified),
+            owner,
+            file.path.display()
+        ));
+    }
+    print_box(
+        &format!("{} {} Files", label, display_count),
+        &file_list,
+        Color::Cyan,
+    );
+    println!();
+}
+
+fn ranked_dirs(dir_sizes: &HashMap<PathBuf, u64>) -> Vec<(&PathBuf, &u64)> {
+    let mut dirs: Vec<(&PathBuf, &u64)> = dir_sizes.iter().collect();
+    dirs.sort_unstable_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    dirs
+}
+
+fn print_top_dirs(dir_sizes: &HashMap<PathBuf, u64>, top_n: usize) {
+    if dir_sizes.is_empty() {
+        let content = vec!["No directories found matching criteria.".to_string()];
+        print_box("Result", &content, Color::Yellow);
+        println!();
+        return;
+    }
+
+    let dirs = ranked_dirs(dir_sizes);
+
+    let display_count = std::cmp::min(top_n, dirs.len());
+    let mut dir_list: Vec<String> = Vec::with_capacity(display_count);
+    for (i, (path, size)) in dirs.iter().take(display_count).enumerate() {
+        dir_list.push(format!(
+            "{:>3}. {:>10}  {}",
+            i + 1,
+            format_size(**size),
+            path.display()
+        ));
+    }
+    print_box(
+        &format!("Top {} Directories", display_count),
+        &dir_list,
+        Color::Cyan,
+    );
+    println!();
+}
+
+/// Lays out the `top_n` largest files as a tree below their scan roots, with
+/// each directory annotated by the combined size of all matched files within
+/// it. Returns how many files are shown and the lines to print.
+fn file_tree(files: &[FileInfo], config: &Config) -> (usize, Vec<String>) {
+    let roots = &config.scan.roots;
+    // Each file belongs to the most specific root it was found under.
+    let root_of = |path: &Path| {
+        roots
+            .iter()
+            .filter(|root| path.starts_with(root))
+            .max_by_key(|root| root.components().count())
+            .cloned()
+    };
+
+    let mut sizes: HashMap<PathBuf, u64> = HashMap::new();
+    for file in files {
+        sizes.insert(file.path.clone(), file.size);
+        if let Some(root) = root_of(&file.path) {
+            for dir in file.path.ancestors().skip(1) {
+                *sizes.entry(dir.to_path_buf()).or_insert(0) += file.size;
+                if dir == root {
+                    break;
+                }
+            }
+        }
+    }
+
+    let mut largest: Vec<&FileInfo> = files.iter().collect();
+    largest.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    largest.truncate(config.top_n);
+
+    let mut shown_roots: Vec<PathBuf> = Vec::new();
+    let mut children: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+    for file in &largest {
+        let Some(root) = root_of(&file.path) else {
+            continue;
+        };
+        let mut path = file.path.as_path();
+        while path != root {
+            let Some(parent) = path.parent() else {
+                break;
+            };
+            let siblings = children.entry(parent.to_path_buf()).or_default();
+            if siblings.iter().any(|p| p == path) {
+                break;
+            }
+            siblings.push(path.to_path_buf());
+            path = parent;
+        }
+        if !shown_roots.contains(&root) {
+            shown_roots.push(root);
+        }
+    }
+
+    let mut lines = Vec::new();
+    for root in &shown_roots {
+        lines.push(format!(
+            "{:>10}  {}",
+            format_size(sizes.get(root).copied().unwrap_or(0)),
+            styled_path(root, config.path_style, roots).display()
+        ));
+        push_tree_lines(root, &children, &sizes, "", &mut lines);
+    }
+    (largest.len(), lines)
+}
+
+fn push_tree_lines(
+    dir: &Path,
+    children: &HashMap<PathBuf, Vec<PathBuf>>,
+    sizes: &HashMap<PathBuf, u64>,
+    prefix: &str,
+    lines: &mut Vec<String>,
+) {
+    let Some(entries) = children.get(dir) else {
+        return;
+    };
+    let size = |path: &Path| sizes.get(path).copied().unwrap_or(0);
+    let mut entries: Vec<&PathBuf> = entries.iter().collect();
+    entries.sort_by(|a, b| size(b).cmp(&size(a)).then_with(|| a.cmp(b)));
+
+    let glyphs = box_glyphs();
+    for (i, path) in entries.iter().enumerate() {
+        let last = i + 1 == entries.len();
+        let is_dir = children.contains_key(*path);
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_else(|| path.to_string_lossy());
+        lines.push(format!(
+            "{:>10}  {}{}{}{}",
+            format_size(size(path)),
+            prefix,
+            if last {
+                glyphs.tree_last
+            } else {
+                glyphs.tree_branch
+            },
+            name,
+            if is_dir { "/" } else { "" }
+        ));
+        if is_dir {
+            let pipe = if last { "    " } else { glyphs.tree_pipe };
+            push_tree_lines(path, children, sizes, &format!("{}{}", prefix, pipe), lines);
+        }
+    }
+}
+
+fn print_tree(count: usize, lines: &[String]) {
+    if lines.is_empty() {
+        let content = vec!["No files found matching criteria.".to_string()];
+        print_box("Result", &content, Color::Yellow);
+        println!();
+        return;
+    }
+    print_box(&format!("Tree of Top {} Files", count), lines, Color::Cyan);
+    println!();
+}
+
+/// Matched files sharing an extension or category.
+struct FileGroup {
+    /// Lowercase extension with a leading dot or `(none)`, or a category.
+    name: String,
+    size: u64,
+    count: u64,
+}
+
+/// Extensions of each `--by-category` group, without the leading dot. Files
+/// with any other extension, or none, fall into "Other".
+const CATEGORIES: &[(&str, &[&str])] = &[
+    (
+        "Video",
+        &[
+            "mp4", "mkv", "mov", "avi", "wmv", "flv", "webm", "m4v", "mpg", "mpeg", "3gp", "vob",
+        ],
+    ),
+    (
+        "Image",
+        &[
+            "jpg", "jpeg", "png", "gif", "bmp", "tif", "tiff", "webp", "heic", "svg", "psd", "raw",
+            "cr2", "nef", "dng",
+        ],
+    ),
+    (
+        "Audio",
+        &[
+            "mp3", "wav", "flac", "aac", "ogg", "opus", "m4a", "wma", "aiff",
+        ],
+    ),
+    (
+        "Archive",
+        &[
+            "zip", "tar", "gz", "tgz", "bz2", "xz", "zst", "7z", "rar", "iso", "dmg", "img", "deb",
+            "rpm",
+        ],
+    ),
+    (
+        "Document",
+        &[
+            "pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "odt", "ods", "odp", "rtf", "txt",
+            "md", "csv", "epub",
+        ],
+    ),
+    (
+        "Code",
+        &[
+            "rs", "c", "h", "cpp", "hpp", "cs", "go", "java", "kt", "py", "rb", "php", "js", "ts",
+            "tsx", "swift", "sh", "sql", "html", "css", "json", "yaml", "yml", "toml", "xml",
+        ],
+    ),
+];
+
+/// The `--by-category` group of `path`, by its lowercase extension.
+fn category(path: &Path) -> &'static str {
+    let Some(ext) = path.extension() else {
+        return "Other";
+    };
+    let ext = ext.to_string_lossy().to_lowercase();
+    CATEGORIES
+        .iter()
+        .find(|(_, exts)| exts.contains(&ext.as_str()))
+        .map_or("Other", |(name, _)| name)
+}
+
+/// Totals `files` per group named by `key`, largest group first.
+fn group_files(files: &[FileInfo], key: impl Fn(&Path) -> String) -> Vec<FileGroup> {
+    let mut totals: HashMap<String, (u64, u64)> = HashMap::new();
+    for file in files {
+        let entry = totals.entry(key(&file.path)).or_insert((0, 0));
+        entry.0 += file.size;
+        entry.1 += 1;
+    }
+
+    let mut groups: Vec<FileGroup> = totals
+        .into_iter()
+        .map(|(name, (size, count))| FileGroup { name, size, count })
+        .collect();
+    groups.sort_unstable_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    groups
+}
+
+fn group_by_extension(files: &[FileInfo]) -> Vec<FileGroup> {
+    group_files(files, |path| match path.extension() {
+        Some(ext) => format!(".{}", ext.to_string_lossy().to_lowercase()),
+        None => "(none)".to_string(),
+    })
+}
+
+fn group_by_category(files: &[FileInfo]) -> Vec<FileGroup> {
+    group_files(files, |path| category(path).to_string())
+}
+
+/// Lists the first `top_n` of `groups` in a box titled after `kind`, e.g.
+/// "Extensions".
+fn print_top_groups(groups: &[FileGroup], top_n: usize, kind: &str) {
+    if groups.is_empty() {
+        let content = vec!["No files found matching criteria.".to_string()];
+        print_box("Result", &content, Color::Yellow);
+        println!();
+        return;
+    }
+
+    let display_count = std::cmp::min(top_n, groups.len());
+    let name_width = groups
+        .iter()
+        .take(display_count)
+        .map(|g| visible_width(&g.name))
+        .max()
+        .unwrap_or(0);
+    let mut group_list: Vec<String> = Vec::with_capacity(display_count);
+    for (i, group) in groups.iter().take(display_count).enumerate() {
+        group_list.push(format!(
+            "{:>3}. {:<name_width$}  {:>10}  {:>6} {}",
+            i + 1,
+            group.name,
+            format_size(group.size),
+            group.count,
+            if group.count == 1 { "file" } else { "files" }
+        ));
+    }
+    print_box(
+        &format!("Top {} {}", display_count, kind),
+        &group_list,
+        Color::Cyan,
+    );
+    println!();
+}
+
+/// Sizes of the matched files saved by `--cache`, keyed by path.
+#[derive(Serialize, Deserialize, Default)]
+struct ScanCache {
+    files: BTreeMap<String, u64>,
+}
+
+/// A file whose size changed between two cached scans. `old` is `None` for
+/// new files and `new` is `None` for files that disappeared.
+struct CacheChange {
+    path: String,
+    old: Option<u64>,
+    new: Option<u64>,
+}
+
+impl CacheChange {
+    /// Size of the change in bytes, for ordering.
+    fn delta(&self) -> u64 {
+        self.new.unwrap_or(0).abs_diff(self.old.unwrap_or(0))
+    }
+}
+
+/// Saves the sizes of `files` to the cache at `path` and returns how they
+/// changed since the cache was last written, or `None` if it did not exist.
+fn update_cache(path: &str, files: &[FileInfo]) -> Result<Option<Vec<CacheChange>>, String> {
+    let shown = path.yellow();
+    let previous: Option<ScanCache> = match fs::read_to_string(path) {
+        Ok(text) => Some(
+            serde_json::from_str(&text)
+                .map_err(|e| format!("Invalid cache file {}: {}", shown, e))?,
+        ),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(format!("Cannot read cache file {}: {}", shown, e)),
+    };
+    let current = ScanCache {
+        files: files
+            .iter()
+            .map(|f| (f.path.to_string_lossy().into_owned(), f.size))
+            .collect(),
+    };
+    let json = serde_json::to_string(&current).map_err(|e| e.to_string())?;
+    fs::write(path, json).map_err(|e| format!("Cannot write cache file {}: {}", shown, e))?;
+
+    let Some(previous) = previous else {
+        return Ok(None);
+    };
+    let mut changes: Vec<CacheChange> = current
+        .files
+        .iter()
+        .filter_map(|(path, &size)| match previous.files.get(path) {
+            Some(&old) if old >= size => None,
+            old => Some(CacheChange {
+                path: path.clone(),
+                old: old.copied(),
+                new: Some(size),
+            }),
+        })
+        .chain(
+            previous
+                .files
+                .iter()
+                .filter(|(path, _)| !current.files.contains_key(*path))
+                .map(|(path, &size)| CacheChange {
+                    path: path.clone(),
+                    old: Some(size),
+                    new: None,
+                }),
+        )
+        .collect();
+    // New files first, then grown and gone ones, each largest change first.
+    changes.sort_by_key(|c| (c.old.is_some(), c.new.is_none(), Reverse(c.delta())));
+    Ok(Some(changes))
+}
+
+/// Prints up to `top_n` new, grown and vanished files each.
+fn print_cache_changes(changes: &[CacheChange], config: &Config) {
+    let count = |new: bool, gone: bool| {
+        changes
+            .iter()
+            .filter(|c| c.old.is_none() == new && c.new.is_none() == gone)
+            .count()
+    };
+    let (new, gone) = (count(true, false), count(false, true));
+    let grew = changes.len() - new - gone;
+    if changes.is_empty() {
+        let content = vec!["No files were added, grew or disappeared.".to_string()];
+        print_box("Changes Since Last Scan", &content, Color::Cyan);
+        println!();
+        return;
+    }
+
+    let mut content = vec![format!("{} new, {} grew, {} gone", new, grew, gone)];
+    let mut shown = HashMap::new();
+    for change in changes {
+        let (label, sign) = match (change.old, change.new) {
+            (None, _) => ("new", "+"),
+            (_, None) => ("gone", "-"),
+            _ => ("grew", "+"),
+        };
+        let listed = shown.entry(label).or_insert(0);
+        *listed += 1;
+        if *listed > config.top_n {
+            continue;
+        }
+        let path = styled_path(
+            Path::new(&change.path),
+            config.path_style,
+            &config.scan.roots,
+        );
+        let mut line = format!(
+            "{:<4}  {:>11}  {}",
+            label,
+            format!("{}{}", sign, format_size(change.delta())),
+            path.display()
+        );
+        if let (Some(_), Some(size)) = (change.old, change.new) {
+            line.push_str(&format!("  (now {})", format_size(size)));
+        }
+        content.push(line);
+    }
+    print_box("Changes Since Last Scan", &content, Color::Cyan);
+    println!();
+}
+
+fn print_empty(files: &[FileInfo], empty_dirs: &[PathBuf], top_n: usize) {
+    let mut paths: Vec<&Path> = files.iter().map(|f| f.path.as_path()).collect();
+    paths.sort_unstable();
+
+    let mut content = vec![
+        format!("Empty files     : {}", paths.len()),
+        format!("Empty dirs      : {}", empty_dirs.len()),
+    ];
+    if !paths.is_empty() {
+        content.push(String::new());
+        content.extend(
+            paths
+                .iter()
+                .take(top_n)
+                .map(|p| format!("  {}", p.display())),
+        );
+        if paths.len() > top_n {
+            content.push(format!("  ... and {} more files", paths.len() - top_n));
+        }
+    }
+    if !empty_dirs.is_empty() {
+        content.push(String::new());
+        content.extend(
+            empty_dirs
+                .iter()
+                .take(top_n)
+                .map(|d| format!("  {}{}", d.display(), std::path::MAIN_SEPARATOR)),
+        );
+        if empty_dirs.len() > top_n {
+            content.push(format!(
+                "  ... and {} more directories",
+                empty_dirs.len() - top_n
+            ));
+        }
+    }
+    print_box("Empty Files and Directories", &content, Color::Cyan);
+    println!();
+}
+
+/// Space reclaimable by keeping one copy per group, and how many sets and
+/// redundant copies there are.
+fn duplicate_totals(groups: &[DuplicateGroup]) -> Vec<String> {
+    let wasted: u64 = groups.iter().map(|g| g.wasted()).sum();
+    let copies: usize = groups.iter().map(|g| g.paths.len() - 1).sum();
+    vec![
+        format!("Wasted space    : {}", format_size(wasted)),
+        format!("Duplicate sets  : {}", groups.len()),
+        format!("Redundant copies: {}", copies),
+    ]
+}
+
+/// Prints only [`duplicate_totals`], for `--duplicates-summary`.
+fn print_duplicate_summary(groups: &[DuplicateGroup]) {
+    print_box("Duplicate Summary", &duplicate_totals(groups), Color::Cyan);
+    println!();
+}
+
+fn print_duplicates(groups: &[DuplicateGroup]) {
+    if groups.is_empty() {
+        let content = vec!["No duplicate files found.".to_string()];
+        print_box("Result", &content, Color::Yellow);
+        println!();
+        return;
+    }
+
+    let mut content = duplicate_totals(groups);
+    for group in groups {
+        content.push(String::new());
+        content.push(format!(
+            "{} copies of {}  (reclaimable: {})",
+            group.paths.len(),
+            format_size(group.size),
+            format_size(group.wasted())
+        ));
+        for path in &group.paths {
+            content.push(format!("  {}", path.display()));
+        }
+    }
+    print_box("Duplicate Files", &content, Color::Cyan);
+    println!();
+}
+
+/// Prints only the combined size of the matched files for `--total-only`,
+/// in bytes for `--size-bytes`.
+fn print_total(report: &Report) -> std::io::Result<()> {
+    let total = report.matched_total();
+    let mut out = std::io::stdout().lock();
+    if report.size_bytes {
+        writeln!(out, "{}", total)?;
+    } else {
+        writeln!(out, "{}", format_size(total))?;
+    }
+    out.flush()
+}
+
+/// Prints bare `<size>\t<path>` lines for `--quiet`, led by the size in
+/// bytes for `--size-bytes`.
+fn print_quiet<T: Display>(
+    entries: impl Iterator<Item = (u64, T)>,
+    size_bytes: bool,
+) -> std::io::Result<()> {
+    let stdout = std::io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    for (size, name) in entries {
+        if size_bytes {
+            write!(out, "{}\t", size)?;
+        }
+        writeln!(out, "{}\t{}", format_size(size), name)?;
+    }
+    out.flush()
+}
+
+/// Prints `<rank>. <size>  <name>` lines for `--plain`, with the rank and
+/// size columns padded to their widest entry. `--size-bytes` adds the size
+/// in bytes after the rank.
+fn print_plain(entries: &[(u64, String)], size_bytes: bool) -> std::io::Result<()> {
+    let sizes: Vec<String> = entries.iter().map(|(size, _)| format_size(*size)).collect();
+    let rank_width = entries.len().to_string().len();
+    let size_width = sizes.iter().map(String::len).max().unwrap_or(0);
+    let bytes_width = entries
+        .iter()
+        .map(|(size, _)| size.to_string().len())
+        .max()
+        .unwrap_or(0);
+    let stdout = std::io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    for (i, ((bytes, name), size)) in entries.iter().zip(&sizes).enumerate() {
+        write!(out, "{:>rank_width$}. ", i + 1)?;
+        if size_bytes {
+            write!(out, "{:>bytes_width$}  ", bytes)?;
+        }
+        writeln!(out, "{:>size_width$}  {}", size, name)?;
+    }
+    out.flush()
+}
+
+/// Prints every path followed by a NUL byte for `--print0`.
+fn print_null(files: &[FileInfo]) -> std::io::Result<()> {
+    let stdout = std::io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    for file in files {
+        out.write_all(file.path.as_os_str().as_encoded_bytes())?;
+        out.write_all(b"\0")?;
+    }
+    out.flush()
+}
+
+enum Choice {
+    Delete,
+    Skip,
+    Quit,
+}
+
+/// Asks about one file until a valid answer is given. End of input quits.
+fn prompt_choice() -> std::io::Result<Choice> {
+    let stdin = std::io::stdin();
+    loop {
+        print!("  {} ", "[d]elete / [s]kip / [q]uit:".yellow());
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        if stdin.lock().read_line(&mut answer)? == 0 {
+            println!();
+            return Ok(Choice::Quit);
+        }
+        match answer.trim().to_ascii_lowercase().as_str() {
+            "d" | "delete" => return Ok(Choice::Delete),
+            "s" | "skip" | "" => return Ok(Choice::Skip),
+            "q" | "quit" => return Ok(Choice::Quit),
+            _ => {}
+        }
+    }
+}
+
+/// Walks the displayed files and deletes the ones the user confirms, moving
+/// them to the trash unless `permanent` is set.
+fn interactive_delete(files: &[FileInfo], top_n: usize, permanent: bool) -> std::io::Result<()> {
+    let count = std::cmp::min(top_n, files.len());
+    let mut reclaimed = 0u64;
+    let mut deleted = 0usize;
+    for (i, file) in files.iter().take(count).enumerate() {
+        println!(
+            "  {} {}  {}",
+            format!("[{}/{}]", i + 1, count).dimmed(),
+            format_size(file.size).cyan(),
+            file.path.display()
+        );
+        match prompt_choice()? {
+            Choice::Delete => {
+                let removed = if permanent {
+                    fs::remove_file(&file.path).map_err(|e| e.to_string())
+                } else {
+                    trash::delete(&file.path).map_err(|e| e.to_string())
+                };
+                match removed {
+                    Ok(()) => {
+                        reclaimed += file.size;
+                        deleted += 1;
+                        println!(
+                            "  {}",
+                            if permanent { "Deleted" } else { "Trashed" }.green()
+                        );
+                    }
+                    Err(e) => println!("  {} {}", "Failed:".red(), e),
+                }
+            }
+            Choice::Skip => {}
+            Choice::Quit => break,
+        }
+    }
+    println!();
+    println!(
+        "  {} {} ({} {} {})",
+        "Reclaimed:".green(),
+        format_size(reclaimed),
+        deleted,
+        if deleted == 1 { "file" } else { "files" },
+        if permanent {
+            "deleted"
+        } else {
+            "moved to trash"
+        }
+    );
+    println!();
+    Ok(())
+}
+
+fn print_markdown(report: &Report) -> std::io::Result<()> {
+    let stdout = std::io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    write_markdown_report(&mut out, report)?;
+    out.flush()
+}
+
+/// Prints `file` as one JSON line for `--ndjson`, flushed right away so
+/// readers see it while the scan goes on.
+fn print_ndjson_entry(file: &FileInfo) {
+    let entry = serde_json::json!({
+        "path": file.path.to_string_lossy(),
+        "size": file.size,
+    });
+    let mut out = std::io::stdout().lock();
+    match writeln!(out, "{}", entry).and_then(|_| out.flush()) {
+        Ok(()) => {}
+        // The reader has seen enough, e.g. `fatcat --ndjson | head`.
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => std::process::exit(0),
+        Err(e) => {
+            eprintln!("{} {}", "Failed:".red(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// A pager that stdout is redirected into until [`Pager::finish`].
+#[cfg(unix)]
+struct Pager {
+    child: Child,
+    /// Duplicate of the original stdout, restored when paging ends.
+    saved_stdout: RawFd,
+    /// The SIGINT action in place before paging, restored when it ends.
+    saved_sigint: libc::sigaction,
+}
+
+/// Starts `$PAGER`, or `less` if unset, and redirects stdout into it. Like
+/// git, `LESS` defaults to `FRX` so colors survive and output that fits on
+/// one screen is printed directly; `always` drops the `F` to page even then.
+/// Returns `None`, leaving stdout as it is, if the pager cannot be started.
+#[cfg(unix)]
+fn start_pager(always: bool) -> Option<Pager> {
+    let command = env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| "less".to_string());
+    // PAGER may carry arguments, e.g. "less -S". Started directly rather
+    // than through a shell, so a missing program fails here instead of
+    // leaving the output in a pipe nobody reads.
+    if command.trim() == "cat" {
+        return None;
+    }
+    let mut words = command.split_whitespace();
+    let program = words.next()?;
+    let mut pager = Command::new(program);
+    pager.args(words).stdin(Stdio::piped());
+    if env::var_os("LESS").is_none() {
+        pager.env("LESS", if always { "RX" } else { "FRX" });
+    }
+    let mut child = pager.spawn().ok()?;
+    let pipe = child.stdin.take()?;
+    let _ = std::io::stdout().flush();
+    // SAFETY: plain descriptor and signal calls on stdout and a pipe we own.
+    // Quitting the pager early ends fatcat quietly through SIGPIPE instead
+    // of a failed print. Ctrl-C belongs to the pager while it runs, so it
+    // is ignored here, as git does.
+    let (saved_stdout, saved_sigint) = unsafe {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+        let mut ignore: libc::sigaction = std::mem::zeroed();
+        ignore.sa_sigaction = libc::SIG_IGN;
+        let mut saved_sigint: libc::sigaction = std::mem::zeroed();
+        libc::sigaction(libc::SIGINT, &ignore, &mut saved_sigint);
+        let saved = libc::dup(libc::STDOUT_FILENO);
+        libc::dup2(pipe.as_raw_fd(), libc::STDOUT_FILENO);
+        (saved, saved_sigint)
+    };
+    Some(Pager {
+        child,
+        saved_stdout,
+        saved_sigint,
+    })
+}
+
+#[cfg(unix)]
+impl Pager {
+    /// Closes the pager's input and waits for the user to quit it.
+    fn finish(mut self) {
+        let _ = std::io::stdout().flush();
+        // SAFETY: puts back the descriptor saved by start_pager, which drops
+        // the last write end of the pipe.
+        unsafe {
+            libc::dup2(self.saved_stdout, libc::STDOUT_FILENO);
+            libc::close(self.saved_stdout);
+        }
+        let _ = self.child.wait();
+        // SAFETY: reinstalls the action saved by start_pager.
+        unsafe {
+            libc::sigaction(libc::SIGINT, &self.saved_sigint, std::ptr::null_mut());
+        }
+    }
+}
+
+#[cfg(not(unix))]
+struct Pager;
+
+#[cfg(not(unix))]
+fn start_pager(_: bool) -> Option<Pager> {
+    None
+}
+
+#[cfg(not(unix))]
+impl Pager {
+    fn finish(self) {}
+}
+
+fn print_html(report: &Report) -> std::io::Result<()> {
+    let stdout = std::io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    write_html_report(&mut out, report)?;
+    out.flush()
+}
+
+fn print_yaml(report: &Report) -> std::io::Result<()> {
+    let stdout = std::io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    write_yaml(&mut out, report)?;
+    out.flush()
+}
+
+/// The totals of a [`Report`] without its file list, for `--stats-only-json`.
+#[derive(Serialize)]
+struct StatsReport<'a> {
+    version: &'a str,
+    roots: &'a [String],
+    files_scanned: u64,
+    dirs_scanned: u64,
+    files_found: u64,
+    /// Combined size of the matched files, counting hard links once with
+    /// `--count-links-once`, as in the other totals.
+    matched_bytes: u64,
+    bytes_scanned: u64,
+    errors: u64,
+    elapsed: f64,
+    interrupted: bool,
+    buckets: Vec<SizeBucket>,
+}
+
+fn print_stats_json(report: &Report) -> std::io::Result<()> {
+    let stats = StatsReport {
+        version: report.version,
+        roots: &report.roots,
+        files_scanned: report.files_scanned,
+        dirs_scanned: report.dirs_scanned,
+        files_found: report.files_found,
+        matched_bytes: report.matched_total(),
+        bytes_scanned: report.bytes_scanned,
+        errors: report.errors,
+        elapsed: report.elapsed,
+        interrupted: report.interrupted,
+        buckets: size_distribution(report.files, report.buckets),
+    };
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    write_json(&mut out, &stats, report.json_pretty)?;
+    out.flush()
+}
+
+fn print_json(report: &Report) -> std::io::Result<()> {
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    write_json(&mut out, report, report.json_pretty)?;
+    out.flush()
+}
+
+/// Writes `value` as one line of JSON, or indented over several lines.
+fn write_json<W: Write, T: Serialize>(w: &mut W, value: &T, pretty: bool) -> std::io::Result<()> {
+    if pretty {
+        serde_json::to_writer_pretty(&mut *w, value)?;
+    } else {
+        serde_json::to_writer(&mut *w, value)?;
+    }
+    writeln!(w)
+}
+
+/// Writes `value` as a YAML document with the same fields as its JSON.
+fn write_yaml<W: Write, T: Serialize>(w: &mut W, value: &T) -> std::io::Result<()> {
+    let value = serde_json::to_value(value)?;
+    if is_yaml_block(&value) {
+        write_yaml_block(w, &value, 0, false)
+    } else {
+        writeln!(w, "{}", yaml_scalar(&value))
+    }
+}
+
+/// Whether `value` is written over several lines rather than inline.
+fn is_yaml_block(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Object(map) => !map.is_empty(),
+        serde_json::Value::Array(items) => !items.is_empty(),
+        _ => false,
+    }
+}
+
+/// Writes a non-empty map or sequence indented by `indent` spaces. With
+/// `inline`, the caller has already written the start of the first line,
+/// e.g. the `- ` of a sequence entry.
+fn write_yaml_block<W: Write>(
+    w: &mut W,
+    value: &serde_json::Value,
+    indent: usize,
+    inline: bool,
+) -> std::io::Result<()> {
+    let pad = " ".repeat(indent);
+    match value {
+        serde_json::Value::Object(map) => {
+            for (i, (key, item)) in map.iter().enumerate() {
+                if i > 0 || !inline {
+                    write!(w, "{}", pad)?;
+                }
+                write!(w, "{}:", yaml_string(key))?;
+                match item {
+                    serde_json::Value::Object(_) if is_yaml_block(item) => {
+                        writeln!(w)?;
+                        write_yaml_block(w, item, indent + 2, false)?;
+                    }
+                    serde_json::Value::Array(_) if is_yaml_block(item) => {
+                        writeln!(w)?;
+                        write_yaml_block(w, item, indent, false)?;
+                    }
+                    _ => writeln!(w, " {}", yaml_scalar(item))?,
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 || !inline {
+                    write!(w, "{}", pad)?;
+                }
+                write!(w, "-")?;
+                if is_yaml_block(item) {
+                    write!(w, " ")?;
+                    write_yaml_block(w, item, indent + 2, true)?;
+                } else {
+                    writeln!(w, " {}", yaml_scalar(item))?;
+                }
+            }
+        }
+        _ => writeln!(w, "{}{}", pad, yaml_scalar(value))?,
+    }
+    Ok(())
+}
+
+/// Formats a value that fits on one line, including empty maps and lists.
+fn yaml_scalar(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => yaml_string(s),
+        serde_json::Value::Object(_) => "{}".to_string(),
+        serde_json::Value::Array(_) => "[]".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Leaves `s` unquoted when YAML reads it back as the same string, and
+/// double-quotes it otherwise. JSON string escapes are valid in YAML.
+fn yaml_string(s: &str) -> String {
+    let plain = s
+        .chars()
+        .all(|c| c.is_alphanumeric() || "/._-+~=() ".contains(c))
+        && s.starts_with(|c: char| c.is_alphabetic() || "/._~".contains(c))
+        && !s.ends_with(' ')
+        && !matches!(
+            s.to_ascii_lowercase().as_str(),
+            "true"
+                | "false"
+                | "yes"
+                | "no"
+                | "on"
+                | "off"
+                | "y"
+                | "n"
+                | "null"
+                | "~"
+                | ".inf"
+                | ".nan"
+        )
+        && s.parse::<f64>().is_err();
+    if plain {
+        s.to_string()
+    } else {
+        serde_json::Value::from(s).to_string()
+    }
+}
+
+struct Config {
+    scan: ScanOptions,
+    output: Option<String>,
+    append: bool,
+    /// File that `--cache` keeps the previous scan in.
+    cache: Option<String>,
+    /// Smallest total a directory needs to be listed by `--dirs`; the file
+    /// size minimum when unset.
+    min_dir_size: Option<u64>,
+    top_n: usize,
+    sort: SortKey,
+    reverse: bool,
+    smallest: bool,
+    newest: bool,
+    oldest: bool,
+    /// Account name or id given to `--user`.
+    user: Option<String>,
+    path_style: PathStyle,
+    /// Ascending size distribution thresholds, see `--buckets`.
+    buckets: Vec<u64>,
+    globs: Vec<String>,
+    exclude_globs: Vec<String>,
+    duplicates: bool,
+    /// Print only the totals of `--duplicates`.
+    duplicates_summary: bool,
+    verbose: bool,
+    /// Time each phase of the run, see `--profile`.
+    profile: bool,
+    summary: bool,
+    empty: bool,
+    tree: bool,
+    by_ext: bool,
+    by_category: bool,
+    histogram: bool,
+    quiet: bool,
+    plain: bool,
+    /// Add raw byte counts to `--quiet`, `--plain` and the text log.
+    size_bytes: bool,
+    print0: bool,
+    stdin: bool,
+    /// Rescan interval in seconds for `--watch`.
+    watch: Option<u64>,
+    /// `--pager` or `--no-pager`, paging long output on a terminal if unset.
+    pager: Option<bool>,
+    fail_if_found: Option<FailThreshold>,
+    progress: bool,
+    interactive: bool,
+    trash: bool,
+    force: bool,
+    json: bool,
+    json_pretty: bool,
+    total_only: bool,
+    stats_json: bool,
+    yaml: bool,
+    html: bool,
+    ndjson: bool,
+    markdown: bool,
+    format: OutputFormat,
+}
+
+/// Walks the command line, splitting long options written as `--name=value`
+/// into the option and its inline value, and clusters of short flags like
+/// `-vs` into one flag at a time.
+struct ArgParser<'a> {
+    args: &'a [String],
+    next: usize,
+    flag: String,
+    inline: Option<&'a str>,
+    /// The short flag cluster the current flag came from.
+    cluster: Option<&'a str>,
+    /// Flags of the cluster not returned yet, or the value of the current one.
+    shorts: Option<&'a str>,
+}
+
+impl<'a> ArgParser<'a> {
+    fn new(args: &'a [String]) -> Self {
+        ArgParser {
+            args,
+            next: 1,
+            flag: String::new(),
+            inline: None,
+            cluster: None,
+            shorts: None,
+        }
+    }
+
+    /// Returns the next option or positional argument.
+    fn next_arg(&mut self) -> Result<Option<String>, String> {
+        if self.inline.is_some() {
+            return Err(format!(
+                "Option '{}' does not take a value.",
+                self.flag.yellow()
+            ));
+        }
+        if let Some(shorts) = self.shorts.take() {
+            return Ok(Some(self.short_flag(shorts)));
+        }
+        let Some(arg) = self.args.get(self.next) else {
+            return Ok(None);
+        };
+        self.next += 1;
+        self.cluster = None;
+        if arg.starts_with("--") {
+            match arg.split_once('=') {
+                Some((flag, value)) => {
+                    self.flag = flag.to_string();
+                    self.inline = Some(value);
                 }
-                config.min_size_mb = args[i]
+                None => self.flag = arg.clone(),
+            }
+        } else if arg.starts_with('-') && arg.chars().count() > 2 {
+            self.cluster = Some(arg);
+            return Ok(Some(self.short_flag(&arg[1..])));
+        } else {
+            self.flag = arg.clone();
+        }
+        Ok(Some(self.flag.clone()))
+    }
+
+    /// Makes the first of `shorts` the current flag and keeps the rest.
+    fn short_flag(&mut self, shorts: &'a str) -> String {
+        let mut chars = shorts.chars();
+        let c = chars.next().unwrap_or_default();
+        self.shorts = Some(chars.as_str()).filter(|rest| !rest.is_empty());
+        self.flag = format!("-{}", c);
+        self.flag.clone()
+    }
+
+    /// Returns the value of the current option, inline, attached to a short
+    /// flag as in `-s500`, or from the next argument. `flag` is how the
+    /// option is named in errors.
+    fn value(&mut self, flag: &str) -> Result<&'a str, String> {
+        let missing = || format!("Option '{}' requires an argument.", flag.yellow());
+        if let Some(value) = self.inline.take() {
+            return if value.is_empty() {
+                Err(missing())
+            } else {
+                Ok(value)
+            };
+        }
+        if let Some(value) = self.shorts.take() {
+            return Ok(value);
+        }
+        let value = self.args.get(self.next).ok_or_else(missing)?;
+        self.next += 1;
+        Ok(value)
+    }
+
+    /// The error for an unrecognized current option.
+    fn unknown(&self) -> String {
+        match self.cluster {
+            Some(cluster) => format!(
+                "Unknown option: '{}' in '{}'",
+                self.flag.yellow(),
+                cluster.yellow()
+            ),
+            None => format!("Unknown option: '{}'", self.flag.yellow()),
+        }
+    }
+
+    /// Returns the value of an option whose argument is optional, which can
+    /// therefore only be given inline as `--name=value`.
+    fn optional_value(&mut self) -> Option<&'a str> {
+        self.inline.take()
+    }
+}
+
+fn parse_extensions(value: &str) -> Result<Vec<String>, String> {
+    let extensions: Vec<String> = value
+        .split(',')
+        .map(|e| e.trim().trim_start_matches('.').to_lowercase())
+        .filter(|e| !e.is_empty())
+        .collect();
+    if extensions.is_empty() {
+        return Err(format!("Invalid extension list: '{}'", value.yellow()));
+    }
+    Ok(extensions)
+}
+
+fn parse_buckets(value: &str) -> Result<Vec<u64>, String> {
+    let mut buckets = value
+        .split(',')
+        .map(str::trim)
+        .filter(|b| !b.is_empty())
+        .map(parse_size)
+        .collect::<Result<Vec<u64>, String>>()?;
+    if buckets.is_empty() {
+        return Err(format!("Invalid bucket list: '{}'", value.yellow()));
+    }
+    buckets.sort_unstable();
+    buckets.dedup();
+    Ok(buckets)
+}
+
+fn parse_regex(value: &str) -> Result<Regex, String> {
+    Regex::new(value).map_err(|e| format!("Invalid regex '{}': {}", value.yellow(), e))
+}
+
+/// Combines `patterns` into one set, or `None` when there are none.
+fn build_globset(patterns: &[String]) -> Result<Option<GlobSet>, String> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern)
+            .map_err(|e| format!("Invalid glob '{}': {}", pattern.yellow(), e.kind()))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map(Some)
+        .map_err(|e| format!("Invalid glob: {}", e))
+}
+
+impl Config {
+    /// Whether `-o -` sends the report to stdout in place of the display.
+    fn log_to_stdout(&self) -> bool {
+        self.output.as_deref() == Some("-")
+    }
+
+    /// Smallest directory total `--dirs` lists.
+    fn dir_size_minimum(&self) -> u64 {
+        self.min_dir_size.unwrap_or(self.scan.min_size)
+    }
+
+    /// Whether every matched file must be kept, rather than only the top N
+    /// needed for display.
+    fn keeps_all_files(&self) -> bool {
+        self.output.is_some()
+            || self.cache.is_some()
+            || self.json
+            || self.stats_json
+            || self.yaml
+            || self.html
+            || self.markdown
+            || self.print0
+            || self.verbose
+            || self.summary
+            || self.by_ext
+            || self.by_category
+            || self.tree
+            || self.empty
+            || self.histogram
+            || self.duplicates
+            || self.scan.roots.len() > 1
+            || self.sort != SortKey::Size
+            || self.reverse
+            || self.top_n == usize::MAX
+    }
+
+    fn age_window(&self) -> Option<String> {
+        match (self.scan.older_than, self.scan.newer_than) {
+            (Some(min), Some(max)) => Some(format!(
+                "older than {}, newer than {}",
+                format_age(min),
+                format_age(max)
+            )),
+            (Some(min), None) => Some(format!("older than {}", format_age(min))),
+            (None, Some(max)) => Some(format!("newer than {}", format_age(max))),
+            (None, None) => None,
+        }
+    }
+
+    fn roots_label(&self) -> String {
+        self.scan
+            .roots
+            .iter()
+            .map(|root| root.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// The value of the last `--color`, given as `--color=WHEN` or
+/// `--color WHEN`. Invalid values are reported by parse_args.
+fn color_arg(args: &[String]) -> Option<&str> {
+    let mut when = None;
+    for (i, arg) in args.iter().enumerate() {
+        if arg == "--color" {
+            when = args.get(i + 1).map(String::as_str);
+        } else if let Some(value) = arg.strip_prefix("--color=") {
+            when = Some(value);
+        }
+    }
+    when
+}
+
+fn init_color(args: &[String]) {
+    match color_arg(args) {
+        Some("always") => {
+            colored::control::set_override(true);
+            return;
+        }
+        Some("never") => {
+            colored::control::set_override(false);
+            return;
+        }
+        _ => {}
+    }
+    let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+        || !std::io::stdout().is_terminal()
+        || args.iter().any(|a| a == "--no-color");
+    if no_color {
+        colored::control::set_override(false);
+    }
+}
+
+/// Falls back to ASCII box drawing for `--ascii` or `TERM=dumb`, and drops
+/// the boxes entirely when stdout is not a terminal.
+fn init_layout(args: &[String]) {
+    let ascii =
+        env::var("TERM").is_ok_and(|term| term == "dumb") || args.iter().any(|a| a == "--ascii");
+    if ascii {
+        ASCII_BOXES.store(true, Ordering::Relaxed);
+    }
+    if !std::io::stdout().is_terminal() {
+        PLAIN_BOXES.store(true, Ordering::Relaxed);
+    } else if !args.iter().any(|a| a == "--full-paths") {
+        if let Some((Width(width), _)) = terminal_size() {
+            // Leave room for the borders and padding around each line, but
+            // never squeeze below the minimum box width.
+            let width = (width as usize).saturating_sub(4).max(40);
+            MAX_LINE_WIDTH.store(width, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Defaults read from a discovered config file or `--config FILE`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileConfig {
+    size: Option<SizeSetting>,
+    top: Option<usize>,
+    exclude: Option<Vec<String>>,
+    verbose: Option<bool>,
+    progress: Option<bool>,
+}
+
+/// `size = "500M"` or a bare number of MB as on the command line.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SizeSetting {
+    Text(String),
+    Number(u64),
+}
+
+/// Where a config file is looked for without `--config`, most preferred
+/// first: `$XDG_CONFIG_HOME`, the platform's config directory, `~/.config`
+/// and `~/.fatcatrc`.
+fn default_config_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(xdg) = env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        paths.push(PathBuf::from(xdg).join("fatcat/config.toml"));
+    }
+    if let Some(config) = dirs::config_dir() {
+        paths.push(config.join("fatcat/config.toml"));
+    }
+    if let Some(home) = dirs::home_dir() {
+        paths.push(home.join(".config/fatcat/config.toml"));
+        paths.push(home.join(".fatcatrc"));
+    }
+    paths
+}
+
+/// Finds the `--config` value before regular parsing so the file can supply
+/// defaults that later flags override.
+fn config_file_arg(args: &[String]) -> Result<Option<&str>, String> {
+    let missing = || format!("Option '{}' requires an argument.", "--config".yellow());
+    for (i, arg) in args.iter().enumerate() {
+        if arg == "--config" {
+            return args
+                .get(i + 1)
+                .map(|v| Some(v.as_str()))
+                .ok_or_else(missing);
+        }
+        if let Some(value) = arg.strip_prefix("--config=") {
+            return if value.is_empty() {
+                Err(missing())
+            } else {
+                Ok(Some(value))
+            };
+        }
+    }
+    Ok(None)
+}
+
+/// Applies the config file to `config`. Without an `explicit` one, the
+/// first existing default file is used unless `discover` is false. Missing
+/// default files are not an error, but a missing `--config` file is.
+fn load_config_file(
+    config: &mut Config,
+    explicit: Option<&str>,
+    discover: bool,
+) -> Result<(), String> {
+    let path = match explicit {
+        Some(path) => PathBuf::from(path),
+        None if !discover => return Ok(()),
+        None => match default_config_paths()
+            .into_iter()
+            .find(|path| path.is_file())
+        {
+            Some(path) => path,
+            None => return Ok(()),
+        },
+    };
+    let shown = path.display().to_string();
+    let text = fs::read_to_string(&path)
+        .map_err(|e| format!("Cannot read config file {}: {}", shown.yellow(), e))?;
+    let file: FileConfig = toml::from_str(&text)
+        .map_err(|e| format!("Invalid config file {}: {}", shown.yellow(), e.message()))?;
+
+    if let Some(size) = file.size {
+        config.scan.min_size = match size {
+            SizeSetting::Text(value) => parse_size(&value)?,
+            SizeSetting::Number(mb) => parse_size(&mb.to_string())?,
+        };
+    }
+    if let Some(top) = file.top {
+        config.top_n = top;
+    }
+    if let Some(exclude) = file.exclude {
+        config.scan.excludes = exclude;
+    }
+    if let Some(verbose) = file.verbose {
+        config.verbose = verbose;
+    }
+    if let Some(progress) = file.progress {
+        config.progress = progress;
+    }
+    Ok(())
+}
+
+/// Splits a `PATH:N` argument into the root and its own depth limit. An
+/// existing path is taken whole, so names that contain a colon still work.
+fn parse_root(arg: &str) -> Result<(PathBuf, Option<usize>), String> {
+    if let Some((path, depth)) = arg.rsplit_once(':') {
+        let numeric = !depth.is_empty() && depth.bytes().all(|b| b.is_ascii_digit());
+        if numeric && !path.is_empty() && !Path::new(arg).exists() {
+            return match depth.parse() {
+                Ok(n) if n > 0 => Ok((PathBuf::from(path), Some(n))),
+                _ => Err(format!("Invalid depth: '{}'", arg.yellow())),
+            };
+        }
+    }
+    Ok((PathBuf::from(arg), None))
+}
+
+/// Reads newline-separated paths for `--stdin`, skipping blank lines.
+fn read_stdin_roots() -> Result<Vec<PathBuf>, String> {
+    let mut roots = Vec::new();
+    for line in std::io::stdin().lock().lines() {
+        let line = line.map_err(|e| format!("Failed to read stdin: {}", e))?;
+        let path = line.trim_end_matches('\r');
+        if !path.trim().is_empty() {
+            roots.push(PathBuf::from(path));
+        }
+    }
+    Ok(roots)
+}
+
+fn parse_args(args: &[String]) -> Result<Config, String> {
+    let mut config = Config {
+        scan: ScanOptions {
+            min_size: 100 * 1024 * 1024,
+            ..Default::default()
+        },
+        output: None,
+        append: false,
+        cache: None,
+        min_dir_size: None,
+        top_n: 20,
+        sort: SortKey::Size,
+        reverse: false,
+        smallest: false,
+        newest: false,
+        oldest: false,
+        user: None,
+        path_style: PathStyle::AsGiven,
+        buckets: DEFAULT_BUCKETS.to_vec(),
+        globs: Vec::new(),
+        exclude_globs: Vec::new(),
+        duplicates: false,
+        duplicates_summary: false,
+        verbose: false,
+        profile: false,
+        summary: false,
+        empty: false,
+        tree: false,
+        by_ext: false,
+        by_category: false,
+        histogram: false,
+        quiet: false,
+        plain: false,
+        size_bytes: false,
+        print0: false,
+        stdin: false,
+        watch: None,
+        pager: None,
+        fail_if_found: None,
+        progress: true,
+        interactive: false,
+        trash: false,
+        force: false,
+        json: false,
+        json_pretty: false,
+        total_only: false,
+        stats_json: false,
+        yaml: false,
+        html: false,
+        ndjson: false,
+        markdown: false,
+        format: OutputFormat::Text,
+    };
+    let discover = !args.iter().any(|arg| arg == "--no-config");
+    load_config_file(&mut config, config_file_arg(args)?, discover)?;
+    // The first --exclude on the command line replaces the file's list.
+    let mut cli_excludes = false;
+
+    let mut args = ArgParser::new(args);
+    while let Some(arg) = args.next_arg()? {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                print_help();
+                std::process::exit(0);
+            }
+            "-V" | "--version" => {
+                println!("fatcat {}", VERSION);
+                std::process::exit(0);
+            }
+            "-s" | "--size" => {
+                let value = args.value("-s, --size")?;
+                config.scan.min_size = parse_size(value)?;
+            }
+            "--min-dir-size" => {
+                config.min_dir_size = Some(parse_size(args.value("--min-dir-size")?)?);
+            }
+            "--max-size" => {
+                config.scan.max_size = Some(parse_size(args.value("--max-size")?)?);
+            }
+            "--include-zero" => {
+                config.scan.include_zero = true;
+            }
+            "--older-than" => {
+                config.scan.older_than = Some(parse_duration(args.value("--older-than")?)?);
+            }
+            "--accessed-before" => {
+                config.scan.accessed_before =
+                    Some(parse_duration(args.value("--accessed-before")?)?);
+            }
+            "--newer-than" => {
+                config.scan.newer_than = Some(parse_duration(args.value("--newer-than")?)?);
+            }
+            "-o" | "--output" => {
+                config.output = Some(args.value("-o, --output")?.to_string());
+            }
+            "--append" => {
+                config.append = true;
+            }
+            "--cache" => {
+                config.cache = Some(args.value("--cache")?.to_string());
+            }
+            "-t" | "--top" => {
+                let value = args.value("-t, --top")?;
+                config.top_n = value
                     .parse()
-                    .map_err(|_| format!("Invalid size value: '{}'", args[i].yellow()))?;
+                    .map_err(|_| format!("Invalid number: '{}'", value.yellow()))?;
+            }
+            "--buckets" => {
+                config.buckets = parse_buckets(args.value("--buckets")?)?;
+            }
+            "--sort" => {
+                config.sort = SortKey::parse(args.value("--sort")?)?;
+            }
+            "-r" | "--reverse" => {
+                config.reverse = true;
+            }
+            "--smallest" => {
+                config.smallest = true;
+            }
+            "--newest" => {
+                config.newest = true;
+            }
+            "--oldest" => {
+                config.oldest = true;
+            }
+            "--absolute" | "--relative" => {
+                let style = if arg == "--absolute" {
+                    PathStyle::Absolute
+                } else {
+                    PathStyle::Relative
+                };
+                if config.path_style != PathStyle::AsGiven && config.path_style != style {
+                    return Err(format!(
+                        "{} cannot be combined with {}",
+                        "--absolute".yellow(),
+                        "--relative".yellow()
+                    ));
+                }
+                config.path_style = style;
+            }
+            "--exclude-ext" => {
+                let value = args.value("--exclude-ext")?;
+                config.scan.excluded_extensions = parse_extensions(value)?;
+            }
+            "--match" => {
+                config.scan.name_pattern = Some(parse_regex(args.value("--match")?)?);
+            }
+            "--match-path" => {
+                config.scan.match_path = true;
+            }
+            "--glob" => {
+                config.globs.push(args.value("--glob")?.to_string());
+            }
+            "--exclude-glob" => {
+                config
+                    .exclude_globs
+                    .push(args.value("--exclude-glob")?.to_string());
+            }
+            "--ext" => {
+                let value = args.value("--ext")?;
+                config.scan.extensions = Some(parse_extensions(value)?);
+            }
+            "--exclude" => {
+                let value = args.value("--exclude")?;
+                if !cli_excludes {
+                    config.scan.excludes.clear();
+                    cli_excludes = true;
+                }
+                config.scan.excludes.extend(
+                    value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|name| !name.is_empty())
+                        .map(String::from),
+                );
+            }
+            "--gitignore" => {
+                config.scan.gitignore = true;
+            }
+            "--ignore-file" => {
+                let value = args.value("--ignore-file")?;
+                let path = PathBuf::from(value);
+                if !path.is_file() {
+                    return Err(format!("Ignore file not found: {}", value.yellow()));
+                }
+                config.scan.ignore_files.push(path);
+            }
+            "--disk-usage" => {
+                config.scan.disk_usage = true;
+            }
+            "--count-links-once" => {
+                config.scan.count_links_once = true;
+            }
+            "-x" | "--one-file-system" => {
+                config.scan.one_file_system = true;
+            }
+            "--no-hidden" => {
+                config.scan.skip_hidden_files = true;
+                config.scan.skip_hidden_dirs = true;
+            }
+            "--skip-hidden-files" => {
+                config.scan.skip_hidden_files = true;
+            }
+            "--skip-hidden-dirs" => {
+                config.scan.skip_hidden_dirs = true;
+            }
+            "-L" | "--follow-links" => {
+                config.scan.follow_links = true;
+            }
+            "--depth" => {
+                let value = args.value("--depth")?;
+                match value.parse() {
+                    Ok(n) if n > 0 => config.scan.max_depth = Some(n),
+                    _ => return Err(format!("Invalid depth: '{}'", value.yellow())),
+                }
+            }
+            "--min-depth" => {
+                let value = args.value("--min-depth")?;
+                match value.parse() {
+                    Ok(n) if n > 0 => config.scan.min_depth = Some(n),
+                    _ => return Err(format!("Invalid depth: '{}'", value.yellow())),
+                }
+            }
+            "-j" | "--threads" => {
+                let value = args.value("-j, --threads")?;
+                match value.parse() {
+                    Ok(n) if n > 0 => config.scan.threads = Some(n),
+                    _ => return Err(format!("Invalid thread count: '{}'", value.yellow())),
+                }
+            }
+            "--dirs" => {
+                config.scan.aggregate_dirs = true;
+            }
+            "--owner" => {
+                config.scan.read_owner = true;
+            }
+            "--user" => {
+                config.user = Some(args.value("--user")?.to_string());
+            }
+            "--tree" => {
+                config.tree = true;
+            }
+            "--empty" => {
+                config.empty = true;
+            }
+            "--by-ext" => {
+                config.by_ext = true;
+            }
+            "--by-category" => {
+                config.by_category = true;
+            }
+            "--duplicates" => {
+                config.duplicates = true;
+            }
+            "--duplicates-summary" => {
+                config.duplicates = true;
+                config.duplicates_summary = true;
+            }
+            "--no-color" | "--ascii" | "--full-paths" => {
+                // Applied by init_color/init_layout before parsing so help and
+                // errors honor them too.
+            }
+            "--color" => {
+                // Applied by init_color, which only needs it to be valid.
+                let value = args.value("--color")?;
+                if !["auto", "always", "never"].contains(&value) {
+                    return Err(format!(
+                        "Invalid color mode: '{}' (expected auto, always or never)",
+                        value.yellow()
+                    ));
+                }
+            }
+            "-v" | "--verbose" => {
+                config.verbose = true;
+            }
+            "--profile" => {
+                config.profile = true;
+                config.scan.profile = true;
+            }
+            "-f" | "--format" => {
+                config.format = OutputFormat::parse(args.value("-f, --format")?)?;
+            }
+            "--summary" => {
+                config.summary = true;
+            }
+            "--si" => {
+                SI_UNITS.store(true, Ordering::Relaxed);
+            }
+            "--no-grouping" => {
+                NO_GROUPING.store(true, Ordering::Relaxed);
+            }
+            "--histogram" => {
+                config.histogram = true;
+            }
+            "-q" | "--quiet" => {
+                config.quiet = true;
+            }
+            "--plain" => {
+                config.plain = true;
+            }
+            "--size-bytes" => {
+                config.size_bytes = true;
+            }
+            "-0" | "--print0" | "--null" => {
+                config.print0 = true;
+            }
+            "-i" | "--interactive" => {
+                config.interactive = true;
+            }
+            "--trash" => {
+                config.trash = true;
+            }
+            "--force" => {
+                config.force = true;
             }
-            "-o" | "--output" => {
-                i += 1;
-                if i >= args.len() {
-                    return Err(format!(
-                        "Option '{}' requires an argument.",
-                        "-o, --output".yellow()
-                    ));
-                }
-                config.output = Some(args[i].clone());
+            "--progress" | "--no-progress" => {
+                config.progress = arg == "--progress";
             }
-            "-t" | "--top" => {
-                i += 1;
-                if i >= args.len() {
-                    return Err(format!(
-                        "Option '{}' requires an argument.",
-                        "-t, --top".yellow()
-                    ));
+            "--pager" => {
+                config.pager = Some(true);
+            }
+            "--no-pager" => {
+                config.pager = Some(false);
+            }
+            "--config" => {
+                // Loaded by load_config_file before the other flags.
+                args.value("--config")?;
+            }
+            "--no-config" => {
+                // Checked before the other flags, like --config.
+            }
+            "--stdin" => {
+                config.stdin = true;
+            }
+            "--fail-if-found" => {
+                let threshold = match args.optional_value() {
+                    Some(value) => FailThreshold::parse(value)?,
+                    None => FailThreshold::Count(0),
+                };
+                config.fail_if_found = Some(threshold);
+            }
+            "--watch" => {
+                let value = args.value("--watch")?;
+                match value.parse() {
+                    Ok(n) if n > 0 => config.watch = Some(n),
+                    _ => return Err(format!("Invalid interval: '{}'", value.yellow())),
                 }
-                config.top_n = args[i]
-                    .parse()
-                    .map_err(|_| format!("Invalid number: '{}'", args[i].yellow()))?;
             }
-            "-v" | "--verbose" => {
-                config.verbose = true;
+            "--json" => {
+                config.json = true;
+            }
+            "--json-pretty" => {
+                config.json = true;
+                config.json_pretty = true;
+            }
+            "--total-only" => {
+                config.total_only = true;
+            }
+            "--stats-only-json" => {
+                config.stats_json = true;
+            }
+            "--yaml" => {
+                config.yaml = true;
+            }
+            "--html" => {
+                config.html = true;
+            }
+            "--ndjson" => {
+                config.ndjson = true;
+            }
+            "--markdown" => {
+                config.markdown = true;
             }
             arg if arg.starts_with('-') => {
-                return Err(format!("Unknown option: '{}'", arg.yellow()));
+                return Err(args.unknown());
             }
             arg => {
-                config.path = arg.to_string();
+                let (root, depth) = parse_root(arg)?;
+                if let Some(depth) = depth {
+                    config.scan.root_depths.insert(root.clone(), depth);
+                }
+                config.scan.roots.push(root);
             }
         }
-        i += 1;
+    }
+
+    if config.trash && config.force {
+        return Err(format!(
+            "{} and {} cannot be combined",
+            "--trash".yellow(),
+            "--force".yellow()
+        ));
+    }
+    if (config.trash || config.force) && !config.interactive {
+        return Err(format!(
+            "{} and {} require {}",
+            "--trash".yellow(),
+            "--force".yellow(),
+            "--interactive".yellow()
+        ));
+    }
+
+    if config.interactive {
+        let conflict = [
+            (config.stdin, "--stdin"),
+            (config.log_to_stdout(), "--output -"),
+            (config.ndjson, "--ndjson"),
+            (config.json, "--json"),
+            (config.stats_json, "--stats-only-json"),
+            (config.total_only, "--total-only"),
+            (config.yaml, "--yaml"),
+            (config.html, "--html"),
+            (config.markdown, "--markdown"),
+            (config.print0, "--print0"),
+            (config.quiet, "--quiet"),
+            (config.plain, "--plain"),
+            (config.summary, "--summary"),
+            (config.scan.aggregate_dirs, "--dirs"),
+            (config.tree, "--tree"),
+            (config.by_ext, "--by-ext"),
+            (config.by_category, "--by-category"),
+            (config.empty, "--empty"),
+            (config.duplicates, "--duplicates"),
+            (config.path_style == PathStyle::Relative, "--relative"),
+            (config.watch.is_some(), "--watch"),
+        ]
+        .into_iter()
+        .find_map(|(set, flag)| set.then_some(flag));
+        if let Some(flag) = conflict {
+            return Err(format!(
+                "--interactive cannot be combined with {}",
+                flag.yellow()
+            ));
+        }
+    }
+
+    config.scan.include_globs = build_globset(&config.globs)?;
+    config.scan.exclude_globs = build_globset(&config.exclude_globs)?;
+
+    if config.ndjson {
+        let conflict = [
+            (config.output.is_some(), "--output"),
+            (config.cache.is_some(), "--cache"),
+        ]
+        .into_iter()
+        .find_map(|(set, flag)| set.then_some(flag));
+        if let Some(flag) = conflict {
+            return Err(format!(
+                "--ndjson cannot be combined with {}",
+                flag.yellow()
+            ));
+        }
+    }
+
+    if config.append && config.log_to_stdout() {
+        return Err(format!(
+            "--append cannot be combined with {}",
+            "--output -".yellow()
+        ));
+    }
+
+    if config.append && config.format == OutputFormat::Html {
+        return Err(format!(
+            "--append cannot be combined with {}",
+            "--format html".yellow()
+        ));
+    }
+
+    if config.pager == Some(true) {
+        let conflict = [
+            (config.interactive, "--interactive"),
+            (config.ndjson, "--ndjson"),
+            (config.watch.is_some(), "--watch"),
+        ]
+        .into_iter()
+        .find_map(|(set, flag)| set.then_some(flag));
+        if let Some(flag) = conflict {
+            return Err(format!("--pager cannot be combined with {}", flag.yellow()));
+        }
+    }
+
+    if config.watch.is_some() && config.fail_if_found.is_some() {
+        return Err(format!(
+            "--fail-if-found cannot be combined with {}",
+            "--watch".yellow()
+        ));
+    }
+
+    if let Some(ref user) = config.user {
+        config.scan.owner_uid = user_id(user)?;
+    }
+
+    if config.scan.match_path && config.scan.name_pattern.is_none() {
+        return Err(format!("--match-path requires {}", "--match".yellow()));
+    }
+
+    for (set, flag) in [(config.newest, "--newest"), (config.oldest, "--oldest")] {
+        if !set {
+            continue;
+        }
+        let conflict = [
+            (config.sort != SortKey::Size, "--sort"),
+            (config.reverse, "--reverse"),
+            (config.smallest, "--smallest"),
+            (config.newest, "--newest"),
+        ]
+        .into_iter()
+        .find(|&(set, other)| set && other != flag);
+        if let Some((_, other)) = conflict {
+            return Err(format!(
+                "{} cannot be combined with {}",
+                flag,
+                other.yellow()
+            ));
+        }
+    }
+    if config.smallest {
+        if config.sort != SortKey::Size {
+            return Err(format!(
+                "--smallest cannot be combined with {}",
+                "--sort".yellow()
+            ));
+        }
+        if config.reverse {
+            return Err(format!(
+                "--smallest cannot be combined with {}",
+                "--reverse".yellow()
+            ));
+        }
+        // Ascending by size, so the first N files are the smallest.
+        config.reverse = true;
+    }
+
+    if config.newest || config.oldest {
+        config.sort = SortKey::Date;
+        // Oldest first; unknown times still come last.
+        config.reverse = config.oldest;
+    }
+
+    if config.empty {
+        // Replaces the size filter rather than combining with it.
+        config.scan.min_size = 0;
+        config.scan.max_size = Some(0);
+        config.scan.include_zero = true;
+        config.scan.find_empty_dirs = true;
+    }
+
+    if config.stdin {
+        let roots = read_stdin_roots()?;
+        if roots.is_empty() {
+            return Err("No paths were read from stdin.".to_string());
+        }
+        config.scan.roots.extend(roots);
+    }
+
+    if config.scan.roots.is_empty() {
+        config.scan.roots.push(PathBuf::from("./"));
+    }
+
+    // --top 0 lists every match.
+    if config.top_n == 0 {
+        config.top_n = usize::MAX;
+    }
+
+    if let Some(max) = config.scan.max_size {
+        if max < config.scan.min_size {
+            return Err(format!(
+                "Maximum size {} is below minimum size {}",
+                format_size(max).yellow(),
+                format_size(config.scan.min_size).yellow()
+            ));
+        }
+    }
+
+    if let (Some(older), Some(newer)) = (config.scan.older_than, config.scan.newer_than) {
+        if older >= newer {
+            return Err(format!(
+                "--older-than {} must be shorter than --newer-than {}",
+                format_age(older).yellow(),
+                format_age(newer).yellow()
+            ));
+        }
     }
 
     Ok(config)
 }
 
+fn validate_paths(config: &Config) -> Result<(), String> {
+    for path in &config.scan.roots {
+        let shown = path.display().to_string();
+        match fs::metadata(path) {
+            Ok(metadata) if metadata.is_dir() || metadata.is_file() => {}
+            Ok(_) => return Err(format!("Not a file or directory: {}", shown.yellow())),
+            Err(_) => return Err(format!("Path not found: {}", shown.yellow())),
+        }
+    }
+    Ok(())
+}
+
 fn main() {
-    let config = match parse_args() {
+    let args: Vec<String> = env::args().collect();
+    init_color(&args);
+    init_layout(&args);
+
+    let mut config = match parse_args(&args).and_then(|c| validate_paths(&c).map(|_| c)) {
         Ok(c) => c,
         Err(e) => {
             print_error(&e);
@@ -372,96 +4115,675 @@ fn main() {
         }
     };
 
-    let min_size_bytes = config.min_size_mb * 1024 * 1024;
+    if cfg!(not(unix)) && config.scan.one_file_system {
+        eprintln!(
+            "{} --one-file-system is only supported on Unix and is ignored.",
+            "Warning:".yellow()
+        );
+        config.scan.one_file_system = false;
+    }
+    if cfg!(not(unix)) && config.user.is_some() {
+        eprintln!(
+            "{} --user is only supported on Unix and is ignored.",
+            "Warning:".yellow()
+        );
+    }
+    if cfg!(not(unix)) && config.scan.read_owner {
+        eprintln!(
+            "{} --owner is only supported on Unix and is ignored.",
+            "Warning:".yellow()
+        );
+        config.scan.read_owner = false;
+    }
+    if cfg!(not(unix)) && config.scan.disk_usage {
+        eprintln!(
+            "{} --disk-usage is only supported on Unix; apparent sizes are used.",
+            "Warning:".yellow()
+        );
+        config.scan.disk_usage = false;
+    }
 
-    println!();
-    println!("{} {}", "fatcat".cyan().bold(), VERSION.dimmed());
-    println!();
-    println!(
-        "  {} {}    {} {} MB",
-        "Target:".dimmed(),
-        config.path.white(),
-        "Min:".dimmed(),
-        config.min_size_mb.to_string().white()
-    );
-    println!();
+    if let Some(threads) = config.scan.threads {
+        // Also caps the pool used for hashing in --duplicates mode.
+        let _ = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global();
+    }
 
-    let start = Instant::now();
-    let file_count = AtomicU64::new(0);
-    let dir_count = AtomicU64::new(0);
+    // In order of precedence, `-o -`, --ndjson, --stats-only-json,
+    // --total-only, --json, --yaml, --markdown, --html, --print0, --quiet and
+    // --plain replace the normal output and silence the decorations.
+    let decorated = !config.log_to_stdout()
+        && !config.ndjson
+        && !config.stats_json
+        && !config.total_only
+        && !config.json
+        && !config.yaml
+        && !config.markdown
+        && !config.html
+        && !config.print0
+        && !config.quiet
+        && !config.plain;
 
-    let files = scan_directory(&config.path, min_size_bytes, &file_count, &dir_count);
+    let tty = std::io::stdout().is_terminal();
+    if !tty {
+        // Spinners and bars would interleave with piped output.
+        config.progress = false;
+    }
 
-    let elapsed = start.elapsed().as_secs_f64();
-    let total_files = file_count.load(Ordering::Relaxed);
-    let total_dirs = dir_count.load(Ordering::Relaxed);
+    if !tty || config.ndjson || config.interactive || config.watch.is_some() {
+        // A pager would hold back streamed lines, steal the prompts' stdin or
+        // fight with the redraws.
+        config.pager = Some(false);
+    }
 
-    println!(
-        "  {} {:.2}s  {} {}  {} {}",
-        "Done:".green(),
-        elapsed,
-        "Scanned:".dimmed(),
-        total_files,
-        "Found:".cyan(),
-        files.len()
-    );
-    println!();
+    if config.ndjson {
+        // Matches are printed as they are found, so none are kept.
+        config.scan.limit = Some(0);
+        config.scan.on_match = Some(OnMatch(Arc::new(print_ndjson_entry)));
+    } else if !config.keeps_all_files() {
+        config.scan.limit = Some(config.top_n);
+    }
 
-    if config.verbose {
-        let gb_count = files.iter().filter(|f| f.size >= 1_073_741_824).count();
-        let mb_500_count = files
-            .iter()
-            .filter(|f| f.size >= 524_288_000 && f.size < 1_073_741_824)
-            .count();
-        let mb_100_count = files
-            .iter()
-            .filter(|f| f.size >= 104_857_600 && f.size < 524_288_000)
-            .count();
-        let total_size: u64 = files.iter().map(|f| f.size).sum();
-
-        let stats = vec![
-            format!("Dirs scanned    : {}", total_dirs),
-            format!("Total size      : {}", format_size(total_size)),
-            format!(">= 1 GB         : {} files", gb_count),
-            format!("500 MB - 1 GB   : {} files", mb_500_count),
-            format!("100 MB - 500 MB : {} files", mb_100_count),
-        ];
-        print_box("Statistics", &stats, Color::Magenta);
-        println!();
+    // Not installed for --watch, where Ctrl-C is how the loop is stopped.
+    let Some(interval) = config.watch else {
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&stop);
+        // The first Ctrl-C ends the scan with what was found so far; a second
+        // one, or any after the scan, quits at once.
+        let handler = ctrlc::set_handler(move || {
+            if flag.swap(true, Ordering::Relaxed) {
+                std::process::exit(130);
+            }
+        });
+        if handler.is_ok() {
+            config.scan.stop = Some(stop);
+        }
+        if run(&config, decorated, tty) {
+            std::process::exit(2);
+        }
+        return;
+    };
+    loop {
+        if tty {
+            // Clear the screen and move the cursor home before redrawing.
+            print!("\x1b[2J\x1b[H");
+        }
+        run(&config, decorated, tty);
+        if decorated {
+            println!(
+                "  {}",
+                format!("Rescanning every {}s. Press Ctrl-C to stop.", interval).dimmed()
+            );
+        }
+        let _ = std::io::stdout().flush();
+        thread::sleep(Duration::from_secs(interval));
     }
+}
 
-    if !files.is_empty() {
-        let display_count = std::cmp::min(config.top_n, files.len());
-        let mut file_list: Vec<String> = Vec::with_capacity(display_count);
-        for (i, file) in files.iter().take(display_count).enumerate() {
-            file_list.push(format!(
-                "{:>3}. {:>10}  {}",
-                i + 1,
-                format_size(file.size),
-                file.path.display()
-            ));
+/// Scans and prints one set of results, then writes the log and runs
+/// interactive deletion if requested. Returns whether the results exceed the
+/// `--fail-if-found` threshold.
+fn run(config: &Config, decorated: bool, tty: bool) -> bool {
+    let min_size_bytes = config.scan.min_size;
+    if decorated && tty {
+        println!();
+        println!("{} {}", "fatcat".cyan().bold(), VERSION.dimmed());
+        println!();
+        print!(
+            "  {} {}    {} {}",
+            "Target:".dimmed(),
+            config.roots_label().white(),
+            "Min:".dimmed(),
+            format_size(min_size_bytes).white()
+        );
+        if let Some(max) = config.scan.max_size {
+            print!("    {} {}", "Max:".dimmed(), format_size(max).white());
         }
-        print_box(&format!("Top {} Files", display_count), &file_list, Color::Cyan);
         println!();
+        println!();
+    }
+
+    let start = Instant::now();
+    // The bar is drawn on stderr; skip the pre-count when nobody sees it.
+    let (mut result, counting) = if decorated && config.progress && std::io::stderr().is_terminal()
+    {
+        scan_with_progress(&config.scan)
     } else {
-        let content = vec!["No files found matching criteria.".to_string()];
-        print_box("Result", &content, Color::Yellow);
+        (scan(&config.scan), Duration::ZERO)
+    };
+    let elapsed = start.elapsed().as_secs_f64();
+    if let Some(ref stop) = config.scan.stop {
+        // Ctrl-C from here on quits rather than being held for the scan.
+        stop.store(true, Ordering::Relaxed);
+    }
+    if result.interrupted && !decorated {
+        eprintln!("{} results are partial", "Interrupted:".yellow());
+    }
+    let mut phases = Vec::new();
+    if config.profile {
+        if !counting.is_zero() {
+            phases.push(("Pre-count", counting));
+        }
+        let walk = start.elapsed() - counting;
+        phases.push(("Traversal", walk.saturating_sub(result.metadata_time)));
+        phases.push(("Metadata", result.metadata_time));
+    }
+
+    let sorting = Instant::now();
+    let mut files = std::mem::take(&mut result.files);
+    sort_files(&mut files, config.sort, config.reverse);
+    phases.push(("Sort", sorting.elapsed()));
+    // Hashing needs the real paths, so it runs before they are restyled.
+    let hashing = Instant::now();
+    let mut duplicates = config.duplicates.then(|| {
+        let spinner = start_spinner("Hashing...", decorated && config.progress);
+        let groups = find_duplicates(&files);
+        spinner.finish_and_clear();
+        groups
+    });
+    if duplicates.is_some() {
+        phases.push(("Hashing", hashing.elapsed()));
+    }
+    // Compared by real path, so it is updated before paths are restyled.
+    // Partial results would make the missing files look deleted next time.
+    let changes = config
+        .cache
+        .as_deref()
+        .filter(|_| !result.interrupted)
+        .map(|path| update_cache(path, &files));
+    // Also needs the real paths to attribute files to roots.
+    let disks = disk_spaces(&config.scan.roots, &files, result.matched_bytes);
+    if config.scan.aggregate_dirs {
+        // Totals already include every subdirectory, so dropping the small
+        // ones after the rollup leaves their parents intact.
+        let min = config.dir_size_minimum();
+        result.dir_sizes.retain(|_, size| *size >= min);
+    }
+    // Built from the real paths, as roots must still prefix them.
+    let tree = (config.tree && decorated && !config.summary).then(|| file_tree(&files, config));
+    if config.path_style != PathStyle::AsGiven {
+        let style = |path: &Path| styled_path(path, config.path_style, &config.scan.roots);
+        for file in &mut files {
+            file.path = style(&file.path);
+        }
+        for group in duplicates.iter_mut().flatten() {
+            for path in &mut group.paths {
+                *path = style(path);
+            }
+        }
+        result.dir_sizes = result
+            .dir_sizes
+            .drain()
+            .map(|(dir, size)| (style(&dir), size))
+            .collect();
+        for dir in &mut result.empty_dirs {
+            *dir = style(dir);
+        }
+    }
+    let files = &files;
+    let total_files = result.files_scanned;
+    let total_dirs = result.dirs_scanned;
+    let found = result.matched;
+
+    let report = Report {
+        version: VERSION,
+        command: env::args()
+            .map(|arg| shell_quote(&arg))
+            .collect::<Vec<_>>()
+            .join(" "),
+        roots: config
+            .scan
+            .roots
+            .iter()
+            .map(|root| root.to_string_lossy().into_owned())
+            .collect(),
+        min_size: min_size_bytes,
+        max_size: config.scan.max_size,
+        files_scanned: total_files,
+        dirs_scanned: total_dirs,
+        files_found: found,
+        matched_bytes: result.matched_bytes,
+        bytes_scanned: result.bytes_scanned,
+        mtime_skipped: result.mtime_skipped,
+        atime_skipped: result.atime_skipped,
+        links_collapsed: result.links_collapsed,
+        linked_bytes: result.linked_bytes,
+        errors: result.errors,
+        elapsed,
+        interrupted: result.interrupted,
+        sort: config.sort,
+        reverse: config.reverse,
+        units: units(),
+        disk_usage: config.scan.disk_usage,
+        disks,
+        buckets: &config.buckets,
+        size_bytes: config.size_bytes,
+        json_pretty: config.json_pretty,
+        files,
+    };
+
+    let output = Instant::now();
+    // Started only now so the progress bar is not drawn over the pager.
+    let pager = match config.pager {
+        Some(false) => None,
+        Some(true) => start_pager(true),
+        None => start_pager(false),
+    };
+
+    if config.log_to_stdout() {
+        // Written as the log below.
+    } else if config.ndjson {
+        // Already printed while scanning.
+    } else if config.stats_json {
+        if let Err(e) = print_stats_json(&report) {
+            eprintln!("{} {}", "Failed:".red(), e);
+            std::process::exit(1);
+        }
+    } else if config.total_only {
+        if let Err(e) = print_total(&report) {
+            eprintln!("{} {}", "Failed:".red(), e);
+            std::process::exit(1);
+        }
+    } else if config.json {
+        if let Err(e) = print_json(&report) {
+            eprintln!("{} {}", "Failed:".red(), e);
+            std::process::exit(1);
+        }
+    } else if config.yaml {
+        if let Err(e) = print_yaml(&report) {
+            eprintln!("{} {}", "Failed:".red(), e);
+            std::process::exit(1);
+        }
+    } else if config.markdown {
+        if let Err(e) = print_markdown(&report) {
+            eprintln!("{} {}", "Failed:".red(), e);
+            std::process::exit(1);
+        }
+    } else if config.html {
+        if let Err(e) = print_html(&report) {
+            eprintln!("{} {}", "Failed:".red(), e);
+            std::process::exit(1);
+        }
+    } else if config.print0 {
+        if let Err(e) = print_null(files) {
+            eprintln!("{} {}", "Failed:".red(), e);
+            std::process::exit(1);
+        }
+    } else if config.quiet || config.plain {
+        let entries: Vec<(u64, String)> = if let Some(ref groups) = duplicates {
+            groups
+                .iter()
+                .flat_map(|g| g.paths.iter().map(|p| (g.size, p.display().to_string())))
+                .collect()
+        } else if config.scan.aggregate_dirs {
+            ranked_dirs(&result.dir_sizes)
+                .into_iter()
+                .take(config.top_n)
+                .map(|(path, size)| (*size, path.display().to_string()))
+                .collect()
+        } else if config.by_ext || config.by_category {
+            let groups = if config.by_ext {
+                group_by_extension(files)
+            } else {
+                group_by_category(files)
+            };
+            groups
+                .into_iter()
+                .take(config.top_n)
+                .map(|group| (group.size, group.name))
+                .collect()
+        } else {
+            files
+                .iter()
+                .take(config.top_n)
+                .map(|f| (f.size, f.path.display().to_string()))
+                .collect()
+        };
+        let printed = if config.quiet {
+            print_quiet(entries.into_iter(), config.size_bytes)
+        } else {
+            print_plain(&entries, config.size_bytes)
+        };
+        if let Err(e) = printed {
+            eprintln!("{} {}", "Failed:".red(), e);
+            std::process::exit(1);
+        }
+    } else {
+        print!(
+            "  {} {}  {} {}  {} {}",
+            "Done:".green(),
+            format_elapsed(elapsed, "s"),
+            "Scanned:".dimmed(),
+            format_count(total_files),
+            "Found:".cyan(),
+            format_count(found)
+        );
+        print!(
+            "  {} {}, {}",
+            "Rate:".dimmed(),
+            format_rate(total_files, elapsed, "files"),
+            format_byte_rate(result.bytes_scanned, elapsed)
+        );
+        if result.errors > 0 {
+            print!("  {} {}", "Unreadable:".red(), format_count(result.errors));
+        }
         println!();
+        println!();
+        if result.interrupted {
+            println!(
+                "  {} the scan was interrupted, so these results are partial.",
+                "Warning:".yellow()
+            );
+            println!();
+        }
+        if result.atime_unreliable() {
+            println!(
+                "  {} access times match modification times for almost every file.",
+                "Warning:".yellow()
+            );
+            println!("  The filesystem may be mounted with noatime, so --accessed-before");
+            println!("  results may be unreliable.");
+            println!();
+        }
+
+        if config.verbose || config.summary {
+            print_statistics(&report, config, &result);
+        }
+        if config.verbose || config.histogram {
+            print_histogram(files);
+        }
+
+        if config.summary {
+            // Totals only; the log, if any, still lists every file.
+        } else if let Some(ref groups) = duplicates {
+            if config.duplicates_summary {
+                print_duplicate_summary(groups);
+            } else {
+                print_duplicates(groups);
+            }
+        } else if config.scan.aggregate_dirs {
+            print_top_dirs(&result.dir_sizes, config.top_n);
+        } else if let Some((count, ref lines)) = tree {
+            print_tree(count, lines);
+        } else if config.by_ext {
+            print_top_groups(&group_by_extension(files), config.top_n, "Extensions");
+        } else if config.by_category {
+            print_top_groups(&group_by_category(files), config.top_n, "Categories");
+        } else if config.empty {
+            print_empty(files, &result.empty_dirs, config.top_n);
+        } else {
+            let label = if config.smallest {
+                "Smallest"
+            } else if config.newest {
+                "Newest"
+            } else if config.oldest {
+                "Oldest"
+            } else {
+                "Top"
+            };
+            print_top_files(files, config.top_n, report.matched_total(), label);
+        }
+        if let Some(Ok(Some(ref changes))) = changes {
+            print_cache_changes(changes, config);
+        }
+    }
+
+    if let Some(Err(ref e)) = changes {
+        eprintln!("{} {}", "Failed:".red(), e);
     }
 
     if let Some(ref log_path) = config.output {
-        match write_log(
-            &files,
-            log_path,
-            &config.path,
-            min_size_bytes,
-            total_files,
-            total_dirs,
-            elapsed,
-        ) {
-            Ok(_) => println!("  {} {}", "Log saved:".green(), log_path),
+        match write_log(&report, log_path, config.format, config.append) {
+            Ok(_) if !decorated => {}
+            Ok(path) => println!("  {} {}", "Log saved:".green(), path.display()),
+            Err(e) if !decorated => eprintln!("{} {}", "Failed:".red(), e),
             Err(e) => println!("  {} {}", "Failed:".red(), e),
         }
-        println!();
+        if decorated {
+            println!();
+        }
+    }
+
+    if config.profile {
+        phases.push(("Output", output.elapsed()));
+        print_profile(&phases, start.elapsed(), decorated);
+    }
+
+    if let Some(pager) = pager {
+        pager.finish();
+    }
+
+    if config.interactive {
+        if let Err(e) = interactive_delete(files, config.top_n, config.force) {
+            println!("  {} {}", "Failed:".red(), e);
+            std::process::exit(1);
+        }
+    }
+
+    if result.interrupted {
+        std::process::exit(130);
+    }
+    config
+        .fail_if_found
+        .is_some_and(|threshold| threshold.exceeded(&report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fatcat::inode_key;
+
+    #[test]
+    fn strip_ansi_removes_sgr() {
+        assert_eq!(strip_ansi("\x1b[1;31mred\x1b[0m text"), "red text");
+    }
+
+    #[test]
+    fn strip_ansi_removes_cursor_and_erase() {
+        assert_eq!(strip_ansi("\x1b[2J\x1b[10;5Hhome"), "home");
+    }
+
+    #[test]
+    fn strip_ansi_removes_intermediate_bytes() {
+        // DECSCUSR: a space before the final byte `q`.
+        assert_eq!(strip_ansi("a\x1b[2 qb"), "ab");
+    }
+
+    #[test]
+    fn strip_ansi_drops_truncated_sequence() {
+        assert_eq!(strip_ansi("done\x1b["), "done");
+    }
+
+    fn args(list: &[&str]) -> Vec<String> {
+        std::iter::once("fatcat")
+            .chain(list.iter().copied())
+            .chain(["--no-config"])
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn parse_size_accepts_large_counts_of_megabytes() {
+        assert_eq!(
+            parse_size("999999999999"),
+            Ok(999_999_999_999 * 1024 * 1024)
+        );
+    }
+
+    #[test]
+    fn parse_size_reads_each_suffix() {
+        assert_eq!(parse_size("512B"), Ok(512));
+        assert_eq!(parse_size("4K"), Ok(4 << 10));
+        assert_eq!(parse_size("4kb"), Ok(4 << 10));
+        assert_eq!(parse_size("4M"), Ok(4 << 20));
+        assert_eq!(parse_size("4MB"), Ok(4 << 20));
+        assert_eq!(parse_size("4G"), Ok(4 << 30));
+        assert_eq!(parse_size("4GB"), Ok(4 << 30));
+        assert_eq!(parse_size("4T"), Ok(4 << 40));
+        assert_eq!(parse_size("4TB"), Ok(4 << 40));
+    }
+
+    #[test]
+    fn parse_size_defaults_to_megabytes() {
+        assert_eq!(parse_size("500"), Ok(500 << 20));
+    }
+
+    #[test]
+    fn parse_size_rounds_fractions() {
+        assert_eq!(parse_size("1.5G"), Ok(1_610_612_736));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn parse_size_rejects_unknown_suffix() {
+        assert!(parse_size("2X").is_err());
+    }
+
+    #[test]
+    fn parse_size_rejects_overflow() {
+        // Too large for u64 on its own.
+        assert!(parse_size("99999999999999999999").is_err());
+        // Fits, but not once multiplied by the unit.
+        assert!(parse_size("17179869184T").is_err());
+        assert!(parse_size("16777215T").is_ok());
+    }
+
+    #[test]
+    fn overflowing_size_is_a_usage_error() {
+        let err = parse_args(&args(&["-s", "99999999999999999999"])).err();
+        assert!(err.is_some_and(|e| strip_ansi(&e).contains("too large")));
+    }
+
+    #[test]
+    fn smallest_conflict_names_the_flag_given() {
+        let err = parse_args(&args(&["--smallest", "--newest"])).err();
+        assert!(err.is_some_and(|e| strip_ansi(&e).ends_with("--smallest")));
+    }
+
+    #[test]
+    fn parse_root_splits_depth_suffix() {
+        assert_eq!(parse_root("/home:2"), Ok((PathBuf::from("/home"), Some(2))));
+        assert_eq!(parse_root("/var"), Ok((PathBuf::from("/var"), None)));
+        assert!(parse_root("/home:0").is_err());
+    }
+
+    #[test]
+    fn parse_root_keeps_other_colons() {
+        // Not a number after the colon.
+        assert_eq!(parse_root("a:b"), Ok((PathBuf::from("a:b"), None)));
+        assert_eq!(parse_root("dir:"), Ok((PathBuf::from("dir:"), None)));
+        assert_eq!(parse_root(":3"), Ok((PathBuf::from(":3"), None)));
+    }
+
+    // Windows does not allow colons in file names.
+    #[cfg(unix)]
+    #[test]
+    fn parse_root_prefers_existing_path() {
+        let dir = env::temp_dir().join(format!("fatcat-colon-{}:2", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let arg = dir.to_str().unwrap();
+        assert_eq!(parse_root(arg), Ok((dir.clone(), None)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn find_duplicates_skips_hard_links() {
+        let dir = env::temp_dir().join(format!("fatcat-links-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let (original, link, copy) = (dir.join("a.mp4"), dir.join("hard.mp4"), dir.join("b.mp4"));
+        fs::write(&original, [7u8; 4096]).unwrap();
+        fs::hard_link(&original, &link).unwrap();
+        fs::copy(&original, &copy).unwrap();
+        let files: Vec<FileInfo> = [&original, &link, &copy]
+            .into_iter()
+            .map(|path| FileInfo {
+                path: path.clone(),
+                size: 4096,
+                modified: None,
+                owner: None,
+                inode: fs::metadata(path).ok().and_then(|m| inode_key(&m)),
+            })
+            .collect();
+
+        let groups = find_duplicates(&files);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].paths, [original, copy]);
+        assert_eq!(groups[0].wasted(), 4096);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn find_duplicates_only_counts_filtered_files() {
+        let dir = env::temp_dir().join(format!("fatcat-filtered-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (name, size) in [
+            ("a.mp4", 4096),
+            ("b.mp4", 4096),
+            ("a.txt", 4096),
+            ("b.txt", 4096),
+            ("small1.mp4", 1024),
+            ("small2.mp4", 1024),
+        ] {
+            fs::write(dir.join(name), vec![7u8; size]).unwrap();
+        }
+        let opts = ScanOptions {
+            roots: vec![dir.clone()],
+            min_size: 2048,
+            extensions: Some(vec!["mp4".to_string()]),
+            ..Default::default()
+        };
+        let result = scan(&opts);
+
+        let groups = find_duplicates(&result.files);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].paths, [dir.join("a.mp4"), dir.join("b.mp4")]);
+        let wasted: u64 = groups.iter().map(DuplicateGroup::wasted).sum();
+        assert_eq!(wasted, 4096);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn visible_width_counts_wide_characters() {
+        assert_eq!(visible_width("動画.mp4"), 8);
+        assert_eq!(visible_width("ab.mp4"), 6);
+    }
+
+    #[test]
+    fn box_rows_align_with_wide_characters() {
+        let content = vec!["1. 動画.mp4".to_string(), "2. ab.mp4".to_string()];
+        let lines = box_lines("Files", &content, Color::Cyan);
+        let widths: Vec<usize> = lines.iter().map(|line| visible_width(line)).collect();
+        assert!(
+            widths.iter().all(|&width| width == widths[0]),
+            "{:?}",
+            widths
+        );
+    }
+
+    #[test]
+    fn yaml_quotes_ambiguous_strings() {
+        assert_eq!(yaml_string("/tmp/a.mp4"), "/tmp/a.mp4");
+        assert_eq!(yaml_string("true"), "\"true\"");
+        assert_eq!(yaml_string("1.5"), "\"1.5\"");
+        assert_eq!(yaml_string("a: b #c"), "\"a: b #c\"");
+        assert_eq!(yaml_string("tab\there"), "\"tab\\there\"");
+    }
+
+    #[test]
+    fn yaml_nests_maps_in_lists() {
+        let value = serde_json::json!({
+            "roots": ["/tmp"],
+            "disks": [],
+            "files": [{"path": "/tmp/a", "size": 1, "owner": {"uid": 0}}],
+        });
+        let mut out = Vec::new();
+        write_yaml(&mut out, &value).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "roots:\n- /tmp\ndisks: []\nfiles:\n- path: /tmp/a\n  size: 1\n  owner:\n    uid: 0\n"
+        );
+    }
+}